@@ -1,25 +1,195 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use reqwest::Client;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 
-#[derive(Debug, Deserialize)]
+use crate::database::{self, settings};
+
+const AW_API: &str = "http://localhost:5600/api/0";
+
+/// Setting holding the host picked by the user (absent = this machine)
+const HOST_SETTING: &str = "aw_hostname";
+/// Setting holding the resolved buckets for that host (JSON)
+const BUCKETS_SETTING: &str = "aw_buckets";
+/// A latest window event that ended longer ago than this means the watcher stopped
+const CURRENT_MAX_AGE_SECS: i64 = 30;
+/// How long after a period ends ActivityWatch may still flush heartbeats into it
+pub const SETTLE_DELAY_SECS: i64 = 60;
+
+#[derive(Debug, Deserialize, Clone)]
 pub struct AwEvent {
+    pub timestamp: DateTime<Utc>,
     pub duration: f64,
     pub data: AwEventData,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone)]
 pub struct AwEventData {
     pub app: Option<String>,
     pub title: Option<String>,
+    /// Set on AFK watcher events: "afk" or "not-afk"
+    pub status: Option<String>,
+    /// Set on browser watcher events, copied onto matching window events
+    pub url: Option<String>,
+}
+
+/// Window events restricted to the time the user was at the machine
+#[derive(Debug)]
+pub struct ActiveEvents {
+    pub events: Vec<AwEvent>,
+    pub afk_seconds: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AwBucket {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub bucket_type: String,
+    pub client: String,
+    pub hostname: String,
+}
+
+#[derive(Deserialize)]
+struct AwInfo {
+    hostname: String,
+}
+
+/// Buckets Anthyre reads from for a single host
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AwBuckets {
+    pub hostname: String,
+    pub window: String,
+    pub afk: Option<String>,
+    pub web: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AwHost {
+    pub hostname: String,
+    pub buckets: Vec<AwBucket>,
+    pub is_current: bool,
+    pub is_selected: bool,
 }
 
-pub async fn get_aw_events(
+/// List every bucket known to the local ActivityWatch server
+pub async fn list_buckets(client: &Client) -> Result<Vec<AwBucket>, String> {
+    let resp = client
+        .get(format!("{}/buckets/", AW_API))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !resp.status().is_success() {
+        return Err(format!("Failed to list AW buckets: {}", resp.status()));
+    }
+
+    let buckets: HashMap<String, AwBucket> = resp.json().await.map_err(|e| e.to_string())?;
+    let mut buckets: Vec<AwBucket> = buckets.into_values().collect();
+    buckets.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(buckets)
+}
+
+/// Hostname of the machine the ActivityWatch server runs on
+pub async fn current_hostname(client: &Client) -> Result<String, String> {
+    let resp = client
+        .get(format!("{}/info", AW_API))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !resp.status().is_success() {
+        return Err(format!("Failed to fetch AW info: {}", resp.status()));
+    }
+
+    let info: AwInfo = resp.json().await.map_err(|e| e.to_string())?;
+    Ok(info.hostname)
+}
+
+/// Pick the window, AFK and web buckets belonging to `hostname`
+fn pick_buckets(buckets: &[AwBucket], hostname: &str) -> Result<AwBuckets, String> {
+    let for_host = |bucket_type: &str| {
+        buckets
+            .iter()
+            .filter(|b| b.bucket_type == bucket_type && b.hostname == hostname)
+            .map(|b| b.id.clone())
+            .collect::<Vec<_>>()
+    };
+
+    let window = for_host("currentwindow")
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("No window watcher bucket found for host {}", hostname))?;
+    let afk = for_host("afkstatus").into_iter().next();
+
+    // Older browser extensions report their host as "unknown"
+    let mut web = for_host("web.tab.current");
+    if web.is_empty() {
+        web = buckets
+            .iter()
+            .filter(|b| b.bucket_type == "web.tab.current" && b.hostname == "unknown")
+            .map(|b| b.id.clone())
+            .collect();
+    }
+
+    Ok(AwBuckets {
+        hostname: hostname.to_string(),
+        window,
+        afk,
+        web,
+    })
+}
+
+/// Discover buckets for `hostname` (or this machine) and persist the choice
+async fn discover_buckets(client: &Client, hostname: Option<String>) -> Result<AwBuckets, String> {
+    let hostname = match hostname {
+        Some(h) => h,
+        None => current_hostname(client).await?,
+    };
+    let buckets = list_buckets(client).await?;
+    let picked = pick_buckets(&buckets, &hostname)?;
+
+    let json = serde_json::to_string(&picked).map_err(|e| e.to_string())?;
+    let conn = database::connection();
+    settings::set(&conn, BUCKETS_SETTING, &json).map_err(|e| e.to_string())?;
+    println!("✅ Using ActivityWatch buckets for host {}", picked.hostname);
+
+    Ok(picked)
+}
+
+/// Buckets to read from, discovering them on first use
+pub async fn resolve_buckets(client: &Client) -> Result<AwBuckets, String> {
+    // Read settings in a short scope so the MutexGuard is dropped before awaits
+    let (selected_host, stored) = {
+        let conn = database::connection();
+        let host = settings::get(&conn, HOST_SETTING).map_err(|e| e.to_string())?;
+        let stored = settings::get(&conn, BUCKETS_SETTING).map_err(|e| e.to_string())?;
+        (host, stored)
+    };
+
+    let stored: Option<AwBuckets> = stored.and_then(|json| serde_json::from_str(&json).ok());
+    match stored {
+        Some(buckets)
+            if selected_host.is_none()
+                || selected_host.as_deref() == Some(buckets.hostname.as_str()) =>
+        {
+            Ok(buckets)
+        }
+        _ => discover_buckets(client, selected_host).await,
+    }
+}
+
+/// Fetch raw events of a single bucket between `start` and `end`
+pub async fn get_bucket_events<T: serde::de::DeserializeOwned>(
     client: &Client,
+    bucket_id: &str,
     start: DateTime<Utc>,
     end: DateTime<Utc>,
-) -> Result<Vec<AwEvent>, String> {
-    let url = "http://localhost:5600/api/0/buckets/aw-watcher-window_DESKTOP-9R9SJ3O/events";
+) -> Result<Vec<T>, String> {
+    let url = format!(
+        "{}/buckets/{}/events",
+        AW_API,
+        urlencoding::encode(bucket_id)
+    );
     let resp = client
         .get(url)
         .query(&[("start", start.to_rfc3339()), ("end", end.to_rfc3339())])
@@ -28,9 +198,227 @@ pub async fn get_aw_events(
         .map_err(|e| e.to_string())?;
 
     if !resp.status().is_success() {
-        return Err(format!("Failed to fetch AW events: {}", resp.status()));
+        return Err(format!(
+            "Failed to fetch AW events from {}: {}",
+            bucket_id,
+            resp.status()
+        ));
     }
 
-    resp.json::<Vec<AwEvent>>().await.map_err(|e| e.to_string())
+    resp.json::<Vec<T>>().await.map_err(|e| e.to_string())
 }
 
+/// Most recent event of a single bucket
+async fn latest_bucket_event(client: &Client, bucket_id: &str) -> Result<Option<AwEvent>, String> {
+    let url = format!(
+        "{}/buckets/{}/events",
+        AW_API,
+        urlencoding::encode(bucket_id)
+    );
+    let resp = client
+        .get(url)
+        .query(&[("limit", "1")])
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !resp.status().is_success() {
+        return Err(format!(
+            "Failed to fetch latest AW event from {}: {}",
+            bucket_id,
+            resp.status()
+        ));
+    }
+
+    let events: Vec<AwEvent> = resp.json().await.map_err(|e| e.to_string())?;
+    Ok(events.into_iter().next())
+}
+
+fn event_end(ev: &AwEvent) -> DateTime<Utc> {
+    ev.timestamp + Duration::milliseconds((ev.duration * 1000.0) as i64)
+}
+
+/// Merged, sorted `[start, end)` intervals of AFK events with the given status
+fn status_intervals(
+    afk_events: &[AwEvent],
+    status: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let mut intervals: Vec<_> = afk_events
+        .iter()
+        .filter(|ev| ev.data.status.as_deref() == Some(status))
+        .map(|ev| (ev.timestamp.max(start), event_end(ev).min(end)))
+        .filter(|(s, e)| s < e)
+        .collect();
+    intervals.sort();
+
+    let mut merged: Vec<(DateTime<Utc>, DateTime<Utc>)> = Vec::new();
+    for (s, e) in intervals {
+        match merged.last_mut() {
+            Some(last) if s <= last.1 => last.1 = last.1.max(e),
+            _ => merged.push((s, e)),
+        }
+    }
+    merged
+}
+
+/// Cut window events down to the parts that overlap the not-AFK intervals
+fn intersect_active(
+    window_events: &[AwEvent],
+    active: &[(DateTime<Utc>, DateTime<Utc>)],
+) -> Vec<AwEvent> {
+    let mut result = Vec::new();
+    for ev in window_events {
+        let ev_end = event_end(ev);
+        for (s, e) in active {
+            let from = ev.timestamp.max(*s);
+            let to = ev_end.min(*e);
+            if from < to {
+                let mut clipped = ev.clone();
+                clipped.timestamp = from;
+                clipped.duration = (to - from).num_milliseconds() as f64 / 1000.0;
+                result.push(clipped);
+            }
+        }
+    }
+    result
+}
+
+/// Copy the tab URL of browser watcher events onto the window events showing that tab
+fn attach_urls(window_events: &mut [AwEvent], web_events: &[AwEvent]) {
+    for ev in window_events.iter_mut() {
+        let (start, end) = (ev.timestamp, event_end(ev));
+        let Some(title) = ev.data.title.as_deref() else {
+            continue;
+        };
+
+        let best = web_events
+            .iter()
+            .filter(|web| web.data.url.is_some())
+            .filter(|web| {
+                web.data
+                    .title
+                    .as_deref()
+                    .is_some_and(|t| !t.is_empty() && title.contains(t))
+            })
+            .map(|web| {
+                let overlap = event_end(web).min(end) - web.timestamp.max(start);
+                (web, overlap)
+            })
+            .filter(|(_, overlap)| *overlap > Duration::zero())
+            .max_by_key(|(_, overlap)| *overlap)
+            .map(|(web, _)| web.data.url.clone());
+
+        if let Some(url) = best {
+            ev.data.url = url;
+        }
+    }
+}
+
+/// Window events with AFK time removed, plus how long the user was AFK
+pub async fn get_active_events(
+    client: &Client,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<ActiveEvents, String> {
+    let buckets = resolve_buckets(client).await?;
+    let mut window: Vec<AwEvent> = get_bucket_events(client, &buckets.window, start, end).await?;
+
+    // URLs are optional extra detail: a missing browser watcher is not an error
+    for web_bucket in &buckets.web {
+        match get_bucket_events::<AwEvent>(client, web_bucket, start, end).await {
+            Ok(web_events) => attach_urls(&mut window, &web_events),
+            Err(e) => eprintln!("⚠️ Skipping browser bucket {}: {}", web_bucket, e),
+        }
+    }
+
+    let Some(afk_bucket) = &buckets.afk else {
+        // No AFK watcher on this host: count everything as active
+        return Ok(ActiveEvents {
+            events: window,
+            afk_seconds: 0.0,
+        });
+    };
+
+    let afk_events: Vec<AwEvent> = get_bucket_events(client, afk_bucket, start, end).await?;
+    let active = status_intervals(&afk_events, "not-afk", start, end);
+    let afk_seconds = status_intervals(&afk_events, "afk", start, end)
+        .iter()
+        .map(|(s, e)| (*e - *s).num_milliseconds() as f64 / 1000.0)
+        .sum();
+
+    Ok(ActiveEvents {
+        events: intersect_active(&window, &active),
+        afk_seconds,
+    })
+}
+
+/// The window in focus right now (with its tab URL when a browser watcher has
+/// one), or `None` while the user is AFK or the window watcher is not running
+pub async fn current_window(client: &Client) -> Result<Option<AwEvent>, String> {
+    let buckets = resolve_buckets(client).await?;
+
+    if let Some(afk_bucket) = &buckets.afk {
+        let afk = latest_bucket_event(client, afk_bucket).await?;
+        if afk.is_some_and(|ev| ev.data.status.as_deref() == Some("afk")) {
+            return Ok(None);
+        }
+    }
+
+    let Some(mut window) = latest_bucket_event(client, &buckets.window).await? else {
+        return Ok(None);
+    };
+    if event_end(&window) < Utc::now() - Duration::seconds(CURRENT_MAX_AGE_SECS) {
+        return Ok(None);
+    }
+
+    for web_bucket in &buckets.web {
+        match latest_bucket_event(client, web_bucket).await {
+            Ok(Some(web)) => attach_urls(std::slice::from_mut(&mut window), &[web]),
+            Ok(None) => {}
+            Err(e) => eprintln!("⚠️ Skipping browser bucket {}: {}", web_bucket, e),
+        }
+    }
+    Ok(Some(window))
+}
+
+/// List hosts that have an ActivityWatch window watcher
+#[tauri::command]
+pub async fn list_aw_hosts() -> Result<Vec<AwHost>, String> {
+    let client = Client::new();
+    let buckets = list_buckets(&client).await?;
+    let current = current_hostname(&client).await.ok();
+    let selected = resolve_buckets(&client).await.ok().map(|b| b.hostname);
+
+    let mut by_host: BTreeMap<String, Vec<AwBucket>> = BTreeMap::new();
+    for bucket in buckets {
+        by_host.entry(bucket.hostname.clone()).or_default().push(bucket);
+    }
+
+    Ok(by_host
+        .into_iter()
+        .filter(|(_, buckets)| buckets.iter().any(|b| b.bucket_type == "currentwindow"))
+        .map(|(hostname, buckets)| AwHost {
+            is_current: current.as_deref() == Some(hostname.as_str()),
+            is_selected: selected.as_deref() == Some(hostname.as_str()),
+            hostname,
+            buckets,
+        })
+        .collect())
+}
+
+/// Select which host's buckets to use; `None` goes back to this machine
+#[tauri::command]
+pub async fn select_aw_host(hostname: Option<String>) -> Result<AwBuckets, String> {
+    {
+        let conn = database::connection();
+        match &hostname {
+            Some(h) => settings::set(&conn, HOST_SETTING, h),
+            None => settings::delete(&conn, HOST_SETTING),
+        }
+        .map_err(|e| e.to_string())?;
+    }
+
+    discover_buckets(&Client::new(), hostname).await
+}
@@ -1,51 +1,509 @@
-use chrono::{DateTime, Utc};
-use reqwest::Client;
-use serde::Serialize;
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use chrono_tz::Tz;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
 
-#[derive(Serialize)]
-struct EventDateTime {
-    dateTime: String,
+use crate::timezone;
+
+const CALENDARS_URL: &str = "https://www.googleapis.com/calendar/v3/calendars";
+const CALENDAR_LIST_URL: &str = "https://www.googleapis.com/calendar/v3/users/me/calendarList";
+
+/// Private extended property tagging every event Anthyre writes
+const BLOCK_KEY_PROPERTY: &str = "anthyreBlockKey";
+
+/// A failed Google API call. Keeps the HTTP status so callers can react to
+/// specific responses without parsing the message.
+#[derive(Debug)]
+pub struct ApiError {
+    /// `None` when no response came back, or the failure wasn't an HTTP one
+    pub status: Option<StatusCode>,
+    pub message: String,
+}
+
+impl ApiError {
+    /// Error for a response that came back with a non-success `status`
+    pub fn http(status: StatusCode, message: String) -> ApiError {
+        ApiError {
+            status: Some(status),
+            message,
+        }
+    }
+
+    pub fn is(&self, status: StatusCode) -> bool {
+        self.status == Some(status)
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(e: reqwest::Error) -> Self {
+        ApiError {
+            status: e.status(),
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<String> for ApiError {
+    fn from(message: String) -> Self {
+        ApiError {
+            status: None,
+            message,
+        }
+    }
+}
+
+impl From<&str> for ApiError {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+impl From<ApiError> for String {
+    fn from(e: ApiError) -> Self {
+        e.message
+    }
 }
 
+/// Start or end of an event: `date_time` for timed events, `date` for all-day ones
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct EventTime {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_time: Option<DateTime<FixedOffset>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<NaiveDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_zone: Option<String>,
+}
+
+impl EventTime {
+    pub fn at(t: DateTime<Utc>) -> EventTime {
+        EventTime {
+            date_time: Some(t.fixed_offset()),
+            ..Default::default()
+        }
+    }
+
+    /// The instant this refers to; all-day dates start at midnight in `tz`
+    pub fn to_utc(&self, tz: &Tz) -> Option<DateTime<Utc>> {
+        if let Some(dt) = self.date_time {
+            return Some(dt.with_timezone(&Utc));
+        }
+        Some(timezone::start_of_day(tz, self.date?))
+    }
+
+    /// Human readable time in `tz`, e.g. "2024-05-02 09:30" or "2024-05-02"
+    pub fn label(&self, tz: &Tz) -> String {
+        match (self.date_time, self.date) {
+            (Some(dt), _) => dt.with_timezone(tz).format("%Y-%m-%d %H:%M").to_string(),
+            (None, Some(date)) => date.format("%Y-%m-%d").to_string(),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Attendee {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    /// "needsAction", "declined", "tentative" or "accepted"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_status: Option<String>,
+    /// Whether this entry is the calendar's owner
+    #[serde(rename = "self", default)]
+    pub is_self: bool,
+    #[serde(default)]
+    pub organizer: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ExtendedProperties {
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub private: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub shared: HashMap<String, String>,
+}
+
+/// An event as returned by `events.list`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CalendarEvent {
+    pub id: String,
+    /// "confirmed", "tentative" or "cancelled" (deleted, in incremental syncs)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub start: EventTime,
+    #[serde(default)]
+    pub end: EventTime,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attendees: Vec<Attendee>,
+    /// Id of the recurring event this is an instance of
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recurring_event_id: Option<String>,
+    /// Start of this instance in the original recurrence, before any move
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_start_time: Option<EventTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extended_properties: Option<ExtendedProperties>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated: Option<String>,
+}
+
+impl CalendarEvent {
+    pub fn title(&self) -> &str {
+        self.summary.as_deref().unwrap_or("No title")
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.status.as_deref() == Some("cancelled")
+    }
+
+    pub fn is_all_day(&self) -> bool {
+        self.start.date_time.is_none() && self.start.date.is_some()
+    }
+
+    /// Whether the calendar's owner declined the invitation
+    pub fn is_declined(&self) -> bool {
+        self.attendees
+            .iter()
+            .any(|a| a.is_self && a.response_status.as_deref() == Some("declined"))
+    }
+
+    /// `[start, end)` in UTC. All-day events span whole days in `tz`; the end
+    /// date is exclusive, as Google sends it.
+    pub fn time_range(&self, tz: &Tz) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let start = self.start.to_utc(tz)?;
+        let end = self.end.to_utc(tz).unwrap_or(start);
+        Some((start, end.max(start)))
+    }
+
+    /// Whether the event was written by Anthyre rather than planned by the user
+    pub fn is_activity_log(&self) -> bool {
+        self.extended_properties
+            .as_ref()
+            .is_some_and(|p| p.private.contains_key(BLOCK_KEY_PROPERTY))
+    }
+}
+
+/// Body of an activity log event Anthyre writes
 #[derive(Serialize)]
-struct CalendarEvent {
+#[serde(rename_all = "camelCase")]
+struct EventBody {
     summary: String,
     description: String,
-    start: EventDateTime,
-    end: EventDateTime,
+    start: EventTime,
+    end: EventTime,
+    extended_properties: ExtendedProperties,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EventsPage {
+    #[serde(default)]
+    items: Vec<CalendarEvent>,
+    next_page_token: Option<String>,
+    next_sync_token: Option<String>,
+}
+
+/// All pages of an `events.list` query
+pub struct EventList {
+    pub events: Vec<CalendarEvent>,
+    /// Present on the last page; resumes an incremental sync
+    pub next_sync_token: Option<String>,
+}
+
+/// One calendar the user can see, from `users/me/calendarList`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CalendarListEntry {
+    pub id: String,
+    #[serde(default)]
+    pub summary: String,
+    #[serde(default)]
+    pub primary: bool,
+    /// "owner", "writer", "reader" or "freeBusyReader"
+    #[serde(default)]
+    pub access_role: String,
+    pub background_color: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CalendarListPage {
+    #[serde(default)]
+    items: Vec<CalendarListEntry>,
+    next_page_token: Option<String>,
+}
+
+/// Every calendar on the user's calendar list
+pub async fn list_calendars(
+    client: &Client,
+    token: &str,
+) -> Result<Vec<CalendarListEntry>, ApiError> {
+    let mut calendars = Vec::new();
+    let mut page_token: Option<String> = None;
+
+    loop {
+        let mut request = client.get(CALENDAR_LIST_URL).bearer_auth(token);
+        if let Some(page) = &page_token {
+            request = request.query(&[("pageToken", page)]);
+        }
+        let resp = request.send().await?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            return Err(ApiError::http(
+                status,
+                format!("Failed to list calendars: {}", status),
+            ));
+        }
+
+        let page: CalendarListPage = resp.json().await?;
+        calendars.extend(page.items);
+        match page.next_page_token {
+            Some(next) => page_token = Some(next),
+            None => return Ok(calendars),
+        }
+    }
+}
+
+/// Events collection URL of a calendar
+fn events_url(calendar_id: &str) -> String {
+    format!(
+        "{}/{}/events",
+        CALENDARS_URL,
+        urlencoding::encode(calendar_id)
+    )
+}
+
+/// Whether a failed request was rejected because its sync token expired;
+/// the caller has to start over with a full sync
+pub fn is_sync_token_expired(err: &ApiError) -> bool {
+    err.is(StatusCode::GONE)
+}
+
+/// Follow every page of an `events.list` query on one calendar
+pub async fn list_events(
+    client: &Client,
+    token: &str,
+    calendar_id: &str,
+    query: &[(&str, String)],
+) -> Result<EventList, ApiError> {
+    let mut events = Vec::new();
+    let mut page_token: Option<String> = None;
+
+    loop {
+        let mut request = client
+            .get(events_url(calendar_id))
+            .bearer_auth(token)
+            .query(query);
+        if let Some(page) = page_token.take() {
+            request = request.query(&[("pageToken", page)]);
+        }
+        let resp = request.send().await?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body_text = resp
+                .text()
+                .await
+                .unwrap_or_else(|_| "<no body>".to_string());
+            return Err(ApiError::http(
+                status,
+                format!("Google Calendar API failed: {} - {}", status, body_text),
+            ));
+        }
+
+        let page: EventsPage = resp.json().await?;
+        events.extend(page.items);
+        match page.next_page_token {
+            Some(next) => page_token = Some(next),
+            None => {
+                return Ok(EventList {
+                    events,
+                    next_sync_token: page.next_sync_token,
+                })
+            }
+        }
+    }
+}
+
+/// Events of one calendar overlapping `[start, end)`, ordered by start
+pub async fn get_calendar_events(
+    client: &Client,
+    token: &str,
+    calendar_id: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<CalendarEvent>, ApiError> {
+    let query = [
+        ("timeMin", start.to_rfc3339()),
+        ("timeMax", end.to_rfc3339()),
+        ("singleEvents", "true".to_string()),
+        ("orderBy", "startTime".to_string()),
+    ];
+    Ok(list_events(client, token, calendar_id, &query)
+        .await?
+        .events)
 }
 
-pub async fn add_calendar_event(
+/// Create a secondary calendar owned by the user; returns its id
+pub async fn create_calendar(
     client: &Client,
     token: &str,
     summary: &str,
+) -> Result<String, ApiError> {
+    let resp = client
+        .post(CALENDARS_URL)
+        .bearer_auth(token)
+        .json(&serde_json::json!({ "summary": summary }))
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        return Err(ApiError::http(
+            status,
+            format!("Failed to create calendar: {}", status),
+        ));
+    }
+
+    let data: Value = resp.json().await?;
+    data["id"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| "Calendar response has no id".into())
+}
+
+/// Deterministic key of the activity block `[start, end)`
+pub fn block_key(start: DateTime<Utc>, end: DateTime<Utc>) -> String {
+    format!("{}-{}", start.timestamp(), end.timestamp())
+}
+
+/// Id of the event previously written for `key`, if it still exists
+async fn find_event_by_key(
+    client: &Client,
+    token: &str,
+    calendar_id: &str,
+    key: &str,
+) -> Result<Option<String>, ApiError> {
+    let resp = client
+        .get(events_url(calendar_id))
+        .bearer_auth(token)
+        .query(&[
+            (
+                "privateExtendedProperty",
+                format!("{}={}", BLOCK_KEY_PROPERTY, key),
+            ),
+            ("maxResults", "1".to_string()),
+        ])
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        return Err(ApiError::http(
+            status,
+            format!("Failed to look up event: {}", status),
+        ));
+    }
+
+    let data: Value = resp.json().await?;
+    Ok(data["items"][0]["id"].as_str().map(str::to_string))
+}
+
+/// PATCH the event `event_id` with `event`, or POST it as a new one; returns its id
+async fn write_event(
+    client: &Client,
+    token: &str,
+    calendar_id: &str,
+    event_id: Option<&str>,
+    event: &EventBody,
+) -> Result<String, ApiError> {
+    let url = events_url(calendar_id);
+    let request = match event_id {
+        Some(id) => client.patch(format!("{}/{}", url, urlencoding::encode(id))),
+        None => client.post(&url),
+    };
+
+    let resp = request.bearer_auth(token).json(event).send().await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        return Err(ApiError::http(status, format!("Failed: {}", status)));
+    }
+
+    let data: Value = resp.json().await?;
+    Ok(data["id"]
+        .as_str()
+        .ok_or("Calendar response has no event id")?
+        .to_string())
+}
+
+/// Create the event for the block `[start, end)` in `calendar_id`, or patch the
+/// one written by an earlier run: `event_id` when it is known, otherwise the
+/// event tagged with the block's key. Returns the Google event id.
+#[allow(clippy::too_many_arguments)]
+pub async fn upsert_calendar_event(
+    client: &Client,
+    token: &str,
+    calendar_id: &str,
+    summary: &str,
     description: &str,
     start: DateTime<Utc>,
     end: DateTime<Utc>,
-) -> Result<(), String> {
-    let event = CalendarEvent {
+    event_id: Option<&str>,
+) -> Result<String, ApiError> {
+    let key = block_key(start, end);
+    let event = EventBody {
         summary: summary.into(),
         description: description.into(),
-        start: EventDateTime {
-            dateTime: start.to_rfc3339(),
-        },
-        end: EventDateTime {
-            dateTime: end.to_rfc3339(),
+        start: EventTime::at(start),
+        end: EventTime::at(end),
+        extended_properties: ExtendedProperties {
+            private: HashMap::from([(BLOCK_KEY_PROPERTY.to_string(), key.clone())]),
+            ..Default::default()
         },
     };
 
-    let resp = client
-        .post("https://www.googleapis.com/calendar/v3/calendars/primary/events")
-        .bearer_auth(token)
-        .json(&event)
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
+    if let Some(id) = event_id {
+        match write_event(client, token, calendar_id, Some(id), &event).await {
+            Ok(id) => {
+                println!("✅ Event updated: {} -> {}", start, end);
+                return Ok(id);
+            }
+            // Deleted in Google Calendar since: look it up by key or write it again
+            Err(e) if e.is(StatusCode::NOT_FOUND) || e.is(StatusCode::GONE) => {}
+            Err(e) => return Err(e),
+        }
+    }
 
-    if resp.status().is_success() {
-        println!("✅ Event created: {} -> {}", start, end);
-        Ok(())
+    let existing = find_event_by_key(client, token, calendar_id, &key).await?;
+    let id = write_event(client, token, calendar_id, existing.as_deref(), &event).await?;
+
+    if existing.is_some() {
+        println!("✅ Event updated: {} -> {}", start, end);
     } else {
-        Err(format!("Failed: {}", resp.status()))
+        println!("✅ Event created: {} -> {}", start, end);
     }
+    Ok(id)
 }
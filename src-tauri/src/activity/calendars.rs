@@ -0,0 +1,323 @@
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use once_cell::sync::Lazy;
+use reqwest::Client;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::activity::{
+    calendar::{create_calendar, list_calendars, ApiError, CalendarEvent, CalendarListEntry},
+    event_cache::{events_between, stored_events},
+    token::with_access_token,
+};
+use crate::database;
+use crate::timezone::user_timezone;
+use crate::users::active_user_id;
+
+/// Calendar Anthyre writes activity logs to (one per user)
+pub const ROLE_ACTIVITY: &str = "activity";
+/// Calendars whose events count as plans
+pub const ROLE_PLAN: &str = "plan";
+
+/// Name of the calendar Anthyre creates for its activity logs
+const ACTIVITY_CALENDAR_NAME: &str = "Anthyre Activity";
+/// Plans are read from here until the user picks calendars
+const DEFAULT_PLAN_CALENDAR: &str = "primary";
+
+/// Held while looking up or creating the activity calendar, so concurrent
+/// pushes can't each create one
+static ENSURE_ACTIVITY: Lazy<tokio::sync::Mutex<()>> = Lazy::new(|| tokio::sync::Mutex::new(()));
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StoredCalendar {
+    pub calendar_id: String,
+    pub summary: Option<String>,
+}
+
+/// A calendar from the user's list, with how Anthyre uses it
+#[derive(Debug, Serialize)]
+pub struct CalendarChoice {
+    #[serde(flatten)]
+    pub calendar: CalendarListEntry,
+    pub is_plan: bool,
+    pub is_activity: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CalendarSettings {
+    pub activity_calendar: Option<StoredCalendar>,
+    pub plan_calendars: Vec<StoredCalendar>,
+}
+
+fn calendars_with_role(
+    conn: &Connection,
+    user_id: i64,
+    role: &str,
+) -> rusqlite::Result<Vec<StoredCalendar>> {
+    let mut stmt = conn.prepare(
+        "SELECT calendar_id, summary FROM user_calendars
+         WHERE user_id = ?1 AND role = ?2
+         ORDER BY id",
+    )?;
+    let rows = stmt.query_map(params![user_id, role], |row| {
+        Ok(StoredCalendar {
+            calendar_id: row.get(0)?,
+            summary: row.get(1)?,
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn activity_calendar(
+    conn: &Connection,
+    user_id: i64,
+) -> rusqlite::Result<Option<StoredCalendar>> {
+    conn.query_row(
+        "SELECT calendar_id, summary FROM user_calendars
+         WHERE user_id = ?1 AND role = ?2",
+        params![user_id, ROLE_ACTIVITY],
+        |row| {
+            Ok(StoredCalendar {
+                calendar_id: row.get(0)?,
+                summary: row.get(1)?,
+            })
+        },
+    )
+    .optional()
+}
+
+/// Calendar ids plans are read from, `primary` when none were chosen
+pub fn plan_calendar_ids(conn: &Connection, user_id: i64) -> rusqlite::Result<Vec<String>> {
+    let ids: Vec<String> = calendars_with_role(conn, user_id, ROLE_PLAN)?
+        .into_iter()
+        .map(|c| c.calendar_id)
+        .collect();
+    if ids.is_empty() {
+        return Ok(vec![DEFAULT_PLAN_CALENDAR.to_string()]);
+    }
+    Ok(ids)
+}
+
+/// Replace the calendars of `role` for a user
+fn replace_calendars(
+    conn: &Connection,
+    user_id: i64,
+    role: &str,
+    calendars: &[StoredCalendar],
+) -> rusqlite::Result<()> {
+    let now = Utc::now().to_rfc3339();
+    conn.execute(
+        "DELETE FROM user_calendars WHERE user_id = ?1 AND role = ?2",
+        params![user_id, role],
+    )?;
+    for cal in calendars {
+        conn.execute(
+            "INSERT OR IGNORE INTO user_calendars (
+                user_id, calendar_id, summary, role, created_at, updated_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+            params![user_id, cal.calendar_id, cal.summary, role, now],
+        )?;
+    }
+    Ok(())
+}
+
+/// Forget the activity calendar when Google no longer has it, so the next
+/// `ensure_activity_calendar` finds or creates another
+pub fn forget_activity_calendar(conn: &Connection, calendar_id: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM user_calendars WHERE user_id = ?1 AND role = ?2 AND calendar_id = ?3",
+        params![active_user_id(conn), ROLE_ACTIVITY, calendar_id],
+    )?;
+    Ok(())
+}
+
+/// Id of the calendar activity logs go to. The first time, an owned
+/// "Anthyre Activity" calendar is reused if one exists, otherwise created.
+pub async fn ensure_activity_calendar(client: &Client, token: &str) -> Result<String, ApiError> {
+    let _guard = ENSURE_ACTIVITY.lock().await;
+    {
+        let conn = database::connection();
+        if let Some(cal) = activity_calendar(&conn, active_user_id(&conn)).map_err(|e| e.to_string())? {
+            return Ok(cal.calendar_id);
+        }
+    }
+
+    let existing = list_calendars(client, token)
+        .await?
+        .into_iter()
+        .find(|c| c.summary == ACTIVITY_CALENDAR_NAME && c.access_role == "owner");
+    let calendar_id = match existing {
+        Some(cal) => cal.id,
+        None => {
+            let id = create_calendar(client, token, ACTIVITY_CALENDAR_NAME).await?;
+            println!("📅 Created calendar '{}'", ACTIVITY_CALENDAR_NAME);
+            id
+        }
+    };
+
+    let conn = database::connection();
+    replace_calendars(
+        &conn,
+        active_user_id(&conn),
+        ROLE_ACTIVITY,
+        &[StoredCalendar {
+            calendar_id: calendar_id.clone(),
+            summary: Some(ACTIVITY_CALENDAR_NAME.to_string()),
+        }],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(calendar_id)
+}
+
+/// Planned events overlapping `[start, end)` from every plan calendar, ordered
+/// by start. Activity logs written by Anthyre and invitations the user
+/// declined are never treated as plans.
+pub async fn plan_events(
+    client: &Client,
+    token: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<CalendarEvent>, ApiError> {
+    let tz = user_timezone();
+    let calendar_ids = {
+        let conn = database::connection();
+        plan_calendar_ids(&conn, active_user_id(&conn)).map_err(|e| e.to_string())?
+    };
+
+    let mut events = Vec::new();
+    for calendar_id in &calendar_ids {
+        events.extend(events_between(client, token, calendar_id, start, end).await?);
+    }
+    Ok(only_plans(events, &tz))
+}
+
+/// Drop what isn't a plan and order by start
+fn only_plans(events: Vec<CalendarEvent>, tz: &Tz) -> Vec<CalendarEvent> {
+    let mut plans: Vec<CalendarEvent> = events
+        .into_iter()
+        .filter(|ev| !ev.is_activity_log() && !ev.is_declined())
+        .collect();
+    plans.sort_by_key(|ev| ev.time_range(tz));
+    plans
+}
+
+/// Planned events from the local cache alone, or `None` when some plan
+/// calendar isn't cached for the whole range
+fn stored_plan_events(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Option<Vec<CalendarEvent>>, String> {
+    let tz = user_timezone();
+    let conn = database::connection();
+    let calendar_ids = plan_calendar_ids(&conn, active_user_id(&conn)).map_err(|e| e.to_string())?;
+
+    let mut events = Vec::new();
+    for calendar_id in &calendar_ids {
+        match stored_events(&conn, calendar_id, start, end).map_err(|e| e.to_string())? {
+            Some(items) => events.extend(items),
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(only_plans(events, &tz)))
+}
+
+/// Planned events for `[start, end)`. Ranges that are already over come from
+/// the local cache when it has them; everything else asks Google.
+pub async fn load_plan_events(
+    client: &Client,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<CalendarEvent>, String> {
+    if end <= Utc::now() {
+        if let Some(events) = stored_plan_events(start, end)? {
+            return Ok(events);
+        }
+    }
+
+    with_access_token(client, |token| async move {
+        plan_events(client, &token, start, end).await
+    })
+    .await
+    .map_err(String::from)
+}
+
+/// Every calendar on the user's Google calendar list, marked with its role here
+#[tauri::command]
+pub async fn list_user_calendars() -> Result<Vec<CalendarChoice>, String> {
+    let client = Client::new();
+    let calendars = with_access_token(&client, |token| {
+        let client = &client;
+        async move { list_calendars(client, &token).await }
+    })
+    .await?;
+
+    let (plan_ids, activity_id) = {
+        let conn = database::connection();
+        (
+            plan_calendar_ids(&conn, active_user_id(&conn)).map_err(|e| e.to_string())?,
+            activity_calendar(&conn, active_user_id(&conn))
+                .map_err(|e| e.to_string())?
+                .map(|c| c.calendar_id),
+        )
+    };
+
+    Ok(calendars
+        .into_iter()
+        .map(|calendar| {
+            // `primary` is stored by alias, not by its real id
+            let is_plan = plan_ids.contains(&calendar.id)
+                || (calendar.primary && plan_ids.iter().any(|id| id == DEFAULT_PLAN_CALENDAR));
+            let is_activity = activity_id.as_deref() == Some(calendar.id.as_str());
+            CalendarChoice {
+                calendar,
+                is_plan,
+                is_activity,
+            }
+        })
+        .collect())
+}
+
+#[tauri::command]
+pub fn get_calendar_settings() -> Result<CalendarSettings, String> {
+    let conn = database::connection();
+    Ok(CalendarSettings {
+        activity_calendar: activity_calendar(&conn, active_user_id(&conn)).map_err(|e| e.to_string())?,
+        plan_calendars: calendars_with_role(&conn, active_user_id(&conn), ROLE_PLAN)
+            .map_err(|e| e.to_string())?,
+    })
+}
+
+/// Choose where activity logs are written. Without a calendar, the dedicated
+/// "Anthyre Activity" calendar is created (or reused) instead.
+#[tauri::command]
+pub async fn set_activity_calendar(calendar: Option<StoredCalendar>) -> Result<String, String> {
+    match calendar {
+        Some(cal) => {
+            let conn = database::connection();
+            replace_calendars(
+                &conn,
+                active_user_id(&conn),
+                ROLE_ACTIVITY,
+                std::slice::from_ref(&cal),
+            )
+            .map_err(|e| e.to_string())?;
+            Ok(cal.calendar_id)
+        }
+        None => {
+            let client = Client::new();
+            with_access_token(&client, |token| {
+                let client = &client;
+                async move { ensure_activity_calendar(client, &token).await }
+            })
+            .await
+            .map_err(String::from)
+        }
+    }
+}
+
+/// Choose which calendars count as plans; an empty list falls back to `primary`
+#[tauri::command]
+pub fn set_plan_calendars(calendars: Vec<StoredCalendar>) -> Result<(), String> {
+    let conn = database::connection();
+    replace_calendars(&conn, active_user_id(&conn), ROLE_PLAN, &calendars).map_err(|e| e.to_string())
+}
@@ -0,0 +1,312 @@
+use chrono::Utc;
+use regex::Regex;
+use rusqlite::{params, Connection, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::activity::activitywatch::{AwEvent, AwEventData};
+use crate::database;
+
+pub const UNCATEGORIZED: &str = "Uncategorized";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Productivity {
+    Productive,
+    Neutral,
+    Distracting,
+}
+
+impl Productivity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Productivity::Productive => "productive",
+            Productivity::Neutral => "neutral",
+            Productivity::Distracting => "distracting",
+        }
+    }
+
+    pub fn parse(value: &str) -> Productivity {
+        match value {
+            "productive" => Productivity::Productive,
+            "distracting" => Productivity::Distracting,
+            _ => Productivity::Neutral,
+        }
+    }
+}
+
+/// What part of an event a rule looks at
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchField {
+    /// App name, case-insensitive, ".exe" ignored
+    App,
+    /// Regex over the window title
+    Title,
+    /// URL domain (subdomains included) from the browser watcher
+    Domain,
+}
+
+impl MatchField {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MatchField::App => "app",
+            MatchField::Title => "title",
+            MatchField::Domain => "domain",
+        }
+    }
+
+    fn parse(value: &str) -> rusqlite::Result<MatchField> {
+        match value {
+            "app" => Ok(MatchField::App),
+            "title" => Ok(MatchField::Title),
+            "domain" => Ok(MatchField::Domain),
+            other => Err(rusqlite::Error::InvalidColumnType(
+                1,
+                format!("match_field '{}'", other),
+                rusqlite::types::Type::Text,
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CategoryRule {
+    pub id: i64,
+    pub match_field: MatchField,
+    pub pattern: String,
+    pub category: String,
+    pub productivity: Productivity,
+    /// Higher wins when several rules match
+    pub priority: i64,
+}
+
+/// Rule fields sent by the UI when creating or editing a rule
+#[derive(Debug, Deserialize)]
+pub struct RuleInput {
+    pub match_field: MatchField,
+    pub pattern: String,
+    pub category: String,
+    pub productivity: Productivity,
+    #[serde(default)]
+    pub priority: i64,
+}
+
+/// Time spent in one category
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CategoryTotal {
+    pub category: String,
+    pub productivity: Productivity,
+    pub seconds: f64,
+}
+
+enum Matcher {
+    App(String),
+    Title(Regex),
+    Domain(String),
+}
+
+/// Compiled rules, best match first
+pub struct Categorizer {
+    rules: Vec<(CategoryRule, Matcher)>,
+}
+
+fn normalize_app(app: &str) -> String {
+    app.trim()
+        .to_lowercase()
+        .trim_end_matches(".exe")
+        .to_string()
+}
+
+/// Host of a URL without a leading "www."
+pub fn url_domain(url: &str) -> Option<String> {
+    let parsed = reqwest::Url::parse(url).ok()?;
+    let host = parsed.host_str()?.to_lowercase();
+    Some(host.trim_start_matches("www.").to_string())
+}
+
+fn compile(rule: &CategoryRule) -> Result<Matcher, String> {
+    match rule.match_field {
+        MatchField::App => Ok(Matcher::App(normalize_app(&rule.pattern))),
+        MatchField::Title => Regex::new(&rule.pattern)
+            .map(Matcher::Title)
+            .map_err(|e| format!("Invalid title pattern '{}': {}", rule.pattern, e)),
+        MatchField::Domain => Ok(Matcher::Domain(
+            rule.pattern
+                .trim()
+                .trim_start_matches("www.")
+                .to_lowercase(),
+        )),
+    }
+}
+
+impl Matcher {
+    fn matches(&self, data: &AwEventData) -> bool {
+        match self {
+            Matcher::App(app) => data.app.as_deref().map(normalize_app).as_deref() == Some(app),
+            Matcher::Title(re) => data.title.as_deref().is_some_and(|t| re.is_match(t)),
+            Matcher::Domain(domain) => data
+                .url
+                .as_deref()
+                .and_then(url_domain)
+                .is_some_and(|host| host == *domain || host.ends_with(&format!(".{}", domain))),
+        }
+    }
+}
+
+impl Categorizer {
+    pub fn new(mut rules: Vec<CategoryRule>) -> Categorizer {
+        rules.sort_by(|a, b| b.priority.cmp(&a.priority).then(a.id.cmp(&b.id)));
+        let rules = rules
+            .into_iter()
+            .filter_map(|rule| match compile(&rule) {
+                Ok(matcher) => Some((rule, matcher)),
+                Err(e) => {
+                    eprintln!("⚠️ Skipping category rule {}: {}", rule.id, e);
+                    None
+                }
+            })
+            .collect();
+        Categorizer { rules }
+    }
+
+    pub fn load(conn: &Connection) -> rusqlite::Result<Categorizer> {
+        Ok(Categorizer::new(list_rules(conn)?))
+    }
+
+    /// Best matching rule for an event, if any
+    pub fn categorize(&self, data: &AwEventData) -> Option<&CategoryRule> {
+        self.rules
+            .iter()
+            .find(|(_, matcher)| matcher.matches(data))
+            .map(|(rule, _)| rule)
+    }
+
+    /// Category and productivity for an event, uncategorized events count as neutral
+    pub fn classify(&self, data: &AwEventData) -> (String, Productivity) {
+        match self.categorize(data) {
+            Some(rule) => (rule.category.clone(), rule.productivity),
+            None => (UNCATEGORIZED.to_string(), Productivity::Neutral),
+        }
+    }
+
+    /// Time per category, largest first
+    pub fn totals(&self, events: &[AwEvent]) -> Vec<CategoryTotal> {
+        let mut totals: BTreeMap<(String, Productivity), f64> = BTreeMap::new();
+        for ev in events {
+            *totals.entry(self.classify(&ev.data)).or_default() += ev.duration;
+        }
+
+        let mut totals: Vec<CategoryTotal> = totals
+            .into_iter()
+            .map(|((category, productivity), seconds)| CategoryTotal {
+                category,
+                productivity,
+                seconds,
+            })
+            .collect();
+        totals.sort_by(|a, b| b.seconds.total_cmp(&a.seconds));
+        totals
+    }
+}
+
+fn rule_from_row(row: &Row) -> rusqlite::Result<CategoryRule> {
+    Ok(CategoryRule {
+        id: row.get(0)?,
+        match_field: MatchField::parse(&row.get::<_, String>(1)?)?,
+        pattern: row.get(2)?,
+        category: row.get(3)?,
+        productivity: Productivity::parse(&row.get::<_, String>(4)?),
+        priority: row.get(5)?,
+    })
+}
+
+pub fn list_rules(conn: &Connection) -> rusqlite::Result<Vec<CategoryRule>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, match_field, pattern, category, productivity, priority
+         FROM category_rules ORDER BY priority DESC, id",
+    )?;
+    let rows = stmt.query_map([], rule_from_row)?;
+    rows.collect()
+}
+
+pub fn insert_rule(conn: &Connection, rule: &RuleInput) -> rusqlite::Result<i64> {
+    let now = Utc::now().to_rfc3339();
+    conn.execute(
+        "INSERT INTO category_rules (
+            match_field, pattern, category, productivity, priority, created_at, updated_at
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+        params![
+            rule.match_field.as_str(),
+            rule.pattern,
+            rule.category,
+            rule.productivity.as_str(),
+            rule.priority,
+            now
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Reject rules that would never match
+fn validate(rule: &RuleInput) -> Result<(), String> {
+    if rule.pattern.trim().is_empty() {
+        return Err("Pattern must not be empty".into());
+    }
+    if rule.category.trim().is_empty() {
+        return Err("Category must not be empty".into());
+    }
+    if rule.match_field == MatchField::Title {
+        Regex::new(&rule.pattern).map_err(|e| format!("Invalid title pattern: {}", e))?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_category_rules() -> Result<Vec<CategoryRule>, String> {
+    let conn = database::connection();
+    list_rules(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn add_category_rule(rule: RuleInput) -> Result<i64, String> {
+    validate(&rule)?;
+    let conn = database::connection();
+    insert_rule(&conn, &rule).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn update_category_rule(id: i64, rule: RuleInput) -> Result<(), String> {
+    validate(&rule)?;
+    let conn = database::connection();
+    let updated = conn
+        .execute(
+            "UPDATE category_rules
+             SET match_field = ?1, pattern = ?2, category = ?3, productivity = ?4,
+                 priority = ?5, updated_at = ?6
+             WHERE id = ?7",
+            params![
+                rule.match_field.as_str(),
+                rule.pattern,
+                rule.category,
+                rule.productivity.as_str(),
+                rule.priority,
+                Utc::now().to_rfc3339(),
+                id
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+
+    if updated == 0 {
+        return Err(format!("No category rule with id {}", id));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn delete_category_rule(id: i64) -> Result<(), String> {
+    let conn = database::connection();
+    conn.execute("DELETE FROM category_rules WHERE id = ?1", params![id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
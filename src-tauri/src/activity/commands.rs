@@ -1,61 +1,138 @@
-use chrono::{DateTime, Duration,Duration as ChronoDuration, Local, Timelike, Utc};
+use chrono::{DateTime, Duration, Utc};
 use reqwest::Client;
-use serde::Deserialize;
-use tokio::time::{sleep_until, Instant};
-use crate::activity::processor;
+use serde::{Deserialize, Serialize};
 
 use crate::activity::{
-    token::get_latest_token,
-    activitywatch::get_aw_events,
-    summarize::{summarize_events, summarize_with_ollama},
-    calendar::add_calendar_event,
+    activitywatch::get_active_events,
+    categorize::Categorizer,
+    summarize::{app_durations, summarize_events, summarize_with_llm},
+    granularity::{block_minutes, block_start, blocks_in},
+    outbox,
+    store::{self, ActivityBlock, NewActivityBlock},
 };
+use crate::database;
+use crate::timezone::user_timezone;
+
+/// Summarize AW data for `[start, end)` and store it. A stored block is reused
+/// once it was summarized with complete data; earlier ones are summarized again.
+/// A failed LLM summary doesn't stop the block from being stored: it is retried
+/// from the stored text the next time the block is processed.
+/// The flag tells whether the stored block was reused unchanged.
+async fn summarize_block(
+    client: &Client,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<(ActivityBlock, bool), String> {
+    let stored = {
+        let conn = database::connection();
+        store::find_block(&conn, start, end).map_err(|e| e.to_string())?
+    };
+    if let Some(mut block) = stored.filter(|b| b.is_final()) {
+        if !block.needs_summary() {
+            println!("ℹ️ Using stored summary for {start} → {end}");
+            return Ok((block, true));
+        }
+        return match summarize_with_llm(client, &block.raw_text).await {
+            Ok(summary) => {
+                let conn = database::connection();
+                store::set_summary(&conn, block.id, &summary).map_err(|e| e.to_string())?;
+                block.llm_summary = Some(summary);
+                block.sync_status = store::SYNC_PENDING.to_string();
+                block.sync_error = None;
+                Ok((block, false))
+            }
+            Err(e) => {
+                eprintln!("⚠️ Summary for {start} → {end} failed again: {e}");
+                Ok((block, true))
+            }
+        };
+    }
 
-// === 6. Entry point ===
-#[tauri::command]
-pub async fn update_hours() -> Result<(), String> {
-    let client = Client::new();
-    let token = get_latest_token()?;
+    let active = get_active_events(client, start, end).await?;
+    let categorizer = {
+        let conn = database::connection();
+        Categorizer::load(&conn).map_err(|e| e.to_string())?
+    };
 
-    if Utc::now() > token.expiry_date {
-        return Err("Token expired".into());
-    }
+    let (title, raw_text) = if active.events.is_empty() {
+        let title = if active.afk_seconds > 0.0 { "AFK" } else { "No Activity" };
+        (title.to_string(), "".to_string())
+    } else {
+        summarize_events(&active.events, &categorizer)
+    };
 
-    // Work in local time to align with user's clock, then convert to UTC for APIs
-    let now_local = Local::now();
+    let llm_summary = if raw_text.is_empty() {
+        None
+    } else {
+        match summarize_with_llm(client, &raw_text).await {
+            Ok(summary) => Some(summary),
+            Err(e) => {
+                eprintln!("⚠️ Summary for {start} → {end} failed, stored without it: {e}");
+                None
+            }
+        }
+    };
 
-    // End = top of current hour (e.g., 4:00 when it's 4:25 local)
-    let end_local = now_local
-        .with_minute(0)
-        .unwrap()
-        .with_second(0)
-        .unwrap()
-        .with_nanosecond(0)
-        .unwrap();
+    let conn = database::connection();
+    store::save_block(
+        &conn,
+        &NewActivityBlock {
+            block_start: start,
+            block_end: end,
+            title,
+            raw_text,
+            llm_summary,
+            app_durations: app_durations(&active.events),
+            afk_seconds: active.afk_seconds,
+            categories: categorizer.totals(&active.events),
+        },
+    )
+    .map(|block| (block, false))
+    .map_err(|e| format!("Failed to store activity block: {}", e))
+}
 
-    // Start = 1 hour before end
-    let start_local = end_local - Duration::hours(1);
+/// Summarize and store one block, then push it to Google Calendar.
+/// Re-running a block updates its existing calendar event instead of adding another;
+/// a failed push stays in the outbox and is retried in the background.
+pub async fn process_block(
+    client: &Client,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<BlockOutcome, String> {
+    let (block, reused) = summarize_block(client, start, end).await?;
+    // A new summary replaces what the calendar event says
+    if reused && block.sync_status == store::SYNC_SYNCED {
+        return Ok(BlockOutcome::Synced);
+    }
 
-    // Convert to UTC for ActivityWatch and Google APIs
-    let start = start_local.with_timezone(&Utc);
-    let end = end_local.with_timezone(&Utc);
+    {
+        let conn = database::connection();
+        outbox::enqueue(&conn, block.id).map_err(|e| e.to_string())?;
+    }
+    match outbox::push_block(client, &block).await {
+        Ok(()) => Ok(BlockOutcome::Synced),
+        Err(e) => Ok(BlockOutcome::Queued(e)),
+    }
+}
 
-    println!("Processing {start} → {end} ...");
+// === 6. Entry point ===
+#[tauri::command]
+pub async fn update_hours() -> Result<(), String> {
+    let client = Client::new();
 
-    let events = get_aw_events(&client, start, end).await?;
-    let (event_title, raw_text) = summarize_events(&events);
-    let description = summarize_with_ollama(&client, &raw_text).await?;
+    // End = start of the current block (e.g., 4:00 when it's 4:25 with hour blocks),
+    // counted in local time to align with the user's clock
+    let minutes = block_minutes();
+    let end = block_start(Utc::now(), minutes);
+    let start = end - Duration::minutes(minutes);
 
-    add_calendar_event(
-        &client,
-        &token.access_token,
-        &event_title,
-        &description,
-        start,
-        end,
-    )
-    .await?;
-    println!("✅ Hour {start} → {end} updated.");
+    println!("Processing {start} → {end} ...");
+
+    if let BlockOutcome::Queued(e) = process_block(&client, start, end).await? {
+        println!("⏳ Block {start} → {end} stored, calendar push queued: {e}");
+        return Ok(());
+    }
+    println!("✅ Block {start} → {end} updated.");
 
     Ok(())
 }
@@ -73,14 +150,30 @@ pub struct RangeArgs {
     pub end: Option<String>,
 }
 
+#[derive(Debug)]
+pub enum BlockOutcome {
+    Synced,
+    /// Stored locally, calendar push failed and was queued
+    Queued(String),
+}
+
+#[derive(Debug, Serialize)]
+pub struct BlockFailure {
+    pub start: String,
+    pub end: String,
+    pub error: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RangeReport {
+    pub synced: usize,
+    pub queued: usize,
+    pub failed: Vec<BlockFailure>,
+}
+
 #[tauri::command]
-pub async fn update_hours_range(args: RangeArgs) -> Result<(), String> {
+pub async fn update_hours_range(args: RangeArgs) -> Result<RangeReport, String> {
     let client = Client::new();
-    let token = get_latest_token()?;
-
-    if Utc::now() > token.expiry_date {
-        return Err("Token expired".into());
-    }
 
     // Expect RFC3339/ISO strings; parse into UTC
     let start = DateTime::parse_from_rfc3339(&args.start_iso)
@@ -94,73 +187,41 @@ pub async fn update_hours_range(args: RangeArgs) -> Result<(), String> {
         return Err("End time must be after start time".into());
     }
 
-    println!("Processing range {} → {} ...", start, end);
+    // Whole blocks on the grid only, and never the block still in progress:
+    // a partial block stored under its own bounds would overlap the full one
+    let minutes = block_minutes();
+    let start = block_start(start, minutes);
+    let end = block_start(end, minutes).min(block_start(Utc::now(), minutes));
 
-    let mut current_start = start;
-    while current_start < end {
-        let current_end = (current_start + ChronoDuration::hours(1)).min(end);
+    println!("Processing range {} → {} ...", start, end);
 
+    let mut report = RangeReport::default();
+    for (current_start, current_end) in blocks_in(&user_timezone(), start, end, minutes) {
         println!("Processing block {} → {} ...", current_start, current_end);
 
-        let events = get_aw_events(&client, current_start, current_end).await?;
-        
-        let (event_title, raw_text) = if events.is_empty() {
-            ("No Activity".to_string(), "".to_string())
-        } else {
-            summarize_events(&events)
-        };
-
-        let description = if raw_text.is_empty() {
-            "No activity recorded for this period.".to_string()
-        } else {
-            summarize_with_ollama(&client, &raw_text).await?
-        };
-
-        add_calendar_event(
-            &client,
-            &token.access_token,
-            &event_title,
-            &description,
-            current_start,
-            current_end,
-        )
-        .await?;
-        
-        current_start = current_end;
+        // One bad block must not lose the rest of the range
+        match process_block(&client, current_start, current_end).await {
+            Ok(BlockOutcome::Synced) => report.synced += 1,
+            Ok(BlockOutcome::Queued(_)) => report.queued += 1,
+            Err(error) => {
+                eprintln!("❌ Block {} → {} failed: {}", current_start, current_end, error);
+                report.failed.push(BlockFailure {
+                    start: current_start.to_rfc3339(),
+                    end: current_end.to_rfc3339(),
+                    error,
+                });
+            }
+        }
     }
 
-    println!("✅ Range {} → {} updated.", start, end);
-
-    Ok(())
-}
-
-// This function is no longer needed since we're using interval-based scheduling in lib.rs
-// Keeping it for potential future use
-pub async fn run_hourly_updates() -> Result<(), String> {
-    let now = Local::now();
-
-    // Round up to the *next* full minute
-    let next_minute =
-        now.with_second(0).unwrap().with_nanosecond(0).unwrap() + ChronoDuration::minutes(1);
-
-    // Sleep until then
-    let wait_duration = (next_minute - now).to_std().unwrap();
-    println!("⏳ Waiting until {next_minute}...");
-
-    sleep_until(Instant::now() + wait_duration).await;
-
-    // Range = last minute
-    let start_str = (next_minute - ChronoDuration::minutes(1)).to_rfc3339();
-    let end_str = next_minute.to_rfc3339();
-
-    let args = crate::activity::RangeArgs {
-        start_iso: start_str.clone(),
-        end_iso: end_str.clone(),
-        date: None,
-        start: None,
-        end: None,
-    };
+    println!(
+        "✅ Range {} → {} updated: {} synced, {} queued, {} failed.",
+        start,
+        end,
+        report.synced,
+        report.queued,
+        report.failed.len()
+    );
 
-    println!("▶️ Running update for {start_str} → {end_str}");
-    crate::activity::update_hours_range(args).await
+    Ok(report)
 }
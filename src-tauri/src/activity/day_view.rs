@@ -0,0 +1,437 @@
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use reqwest::Client;
+use std::collections::BTreeMap;
+
+use crate::activity::{
+    activitywatch::{get_active_events, AwEventData},
+    calendar::CalendarEvent,
+    calendars::plan_events,
+    categorize::{Categorizer, CategoryTotal, Productivity},
+    granularity::block_minutes,
+    models::{
+        Activity, ActivityStatus, CategorySummary, DailySummary, FrontendData, PlannedEvent,
+        PriorityLevel, RealityCheck, Suggestion, TimeBlock,
+    },
+    store,
+    token::with_access_token,
+};
+use crate::database;
+use crate::timezone::{day_range, parse_date, today, user_timezone};
+
+/// Category given to planned events no rule recognizes
+const PLANNED_CATEGORY: &str = "Planned";
+/// Actual/planned ratio at or above which a plan counts as completed
+pub(crate) const COMPLETED_RATIO: f64 = 0.8;
+/// Actual/planned ratio above which a plan counts as overrun
+pub(crate) const OVERRUN_RATIO: f64 = 1.25;
+/// Actual/planned ratio at or above which a plan counts as partially done
+const PARTIAL_RATIO: f64 = 0.25;
+
+/// Actual activity of one timeline block
+struct ActualBlock {
+    categories: Vec<CategoryTotal>,
+    afk_seconds: f64,
+}
+
+fn minutes_between(start: DateTime<Utc>, end: DateTime<Utc>) -> f64 {
+    (end - start).num_seconds().max(0) as f64 / 60.0
+}
+
+/// Overlap of `[a_start, a_end)` and `[b_start, b_end)` in minutes
+pub(crate) fn overlap_minutes(a: (DateTime<Utc>, DateTime<Utc>), b: (DateTime<Utc>, DateTime<Utc>)) -> f64 {
+    minutes_between(a.0.max(b.0), a.1.min(b.1))
+}
+
+/// Planned (non all-day) calendar events
+fn planned_events(
+    events: &[CalendarEvent],
+    categorizer: &Categorizer,
+    tz: &Tz,
+) -> Vec<PlannedEvent> {
+    events
+        .iter()
+        .filter(|ev| !ev.is_all_day())
+        .filter_map(|ev| {
+            let (start, end) = ev.time_range(tz)?;
+            let title = ev.title().to_string();
+
+            let data = AwEventData {
+                app: None,
+                title: Some(title.clone()),
+                status: None,
+                url: None,
+            };
+            let category = categorizer
+                .categorize(&data)
+                .map(|rule| rule.category.clone())
+                .unwrap_or_else(|| PLANNED_CATEGORY.to_string());
+
+            Some(PlannedEvent {
+                title,
+                start,
+                end,
+                duration_minutes: minutes_between(start, end),
+                category,
+            })
+        })
+        .collect()
+}
+
+/// Actual minutes that count towards a plan of `category`. Plans no rule
+/// recognizes are matched against all productive time.
+fn matching_minutes(category: &str, actual: &[CategoryTotal]) -> f64 {
+    actual
+        .iter()
+        .filter(|c| {
+            if category == PLANNED_CATEGORY {
+                c.productivity == Productivity::Productive
+            } else {
+                c.category == category
+            }
+        })
+        .map(|c| c.seconds / 60.0)
+        .sum()
+}
+
+fn distracted_minutes(actual: &[CategoryTotal]) -> f64 {
+    actual
+        .iter()
+        .filter(|c| c.productivity == Productivity::Distracting)
+        .map(|c| c.seconds / 60.0)
+        .sum()
+}
+
+/// Status of a plan given how much matching time was actually spent on it
+fn plan_status(planned_minutes: f64, matched_minutes: f64, distracted: f64) -> ActivityStatus {
+    let ratio = if planned_minutes > 0.0 {
+        matched_minutes / planned_minutes
+    } else {
+        0.0
+    };
+
+    if ratio > OVERRUN_RATIO {
+        ActivityStatus::Overrun
+    } else if ratio >= COMPLETED_RATIO {
+        ActivityStatus::Completed
+    } else if ratio >= PARTIAL_RATIO {
+        ActivityStatus::Partial
+    } else if distracted >= planned_minutes / 2.0 {
+        ActivityStatus::Distracted
+    } else {
+        ActivityStatus::Missed
+    }
+}
+
+/// Build one timeline block.
+///
+/// Planned activities get their status from the matching actual time in the
+/// block. Actual activities are `Distracted` when distracting, `Completed` or
+/// `Overrun` when they match a plan, and `Partial` when they were unplanned.
+fn build_block(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    plans: &[PlannedEvent],
+    actual: &ActualBlock,
+    tz: &Tz,
+) -> TimeBlock {
+    let distracted = distracted_minutes(&actual.categories);
+
+    let planned_activities: Vec<Activity> = plans
+        .iter()
+        .filter_map(|plan| {
+            let minutes = overlap_minutes((plan.start, plan.end), (start, end));
+            if minutes <= 0.0 {
+                return None;
+            }
+            let matched = matching_minutes(&plan.category, &actual.categories);
+            Some(Activity {
+                title: plan.title.clone(),
+                duration_minutes: minutes,
+                category: plan.category.clone(),
+                status: plan_status(minutes, matched, distracted),
+            })
+        })
+        .collect();
+
+    let actual_activities = actual
+        .categories
+        .iter()
+        .map(|c| {
+            let minutes = c.seconds / 60.0;
+            let planned: f64 = planned_activities
+                .iter()
+                .filter(|p| {
+                    p.category == c.category
+                        || (p.category == PLANNED_CATEGORY
+                            && c.productivity == Productivity::Productive)
+                })
+                .map(|p| p.duration_minutes)
+                .sum();
+
+            let status = if c.productivity == Productivity::Distracting {
+                ActivityStatus::Distracted
+            } else if planned <= 0.0 {
+                ActivityStatus::Partial
+            } else if minutes > planned * OVERRUN_RATIO {
+                ActivityStatus::Overrun
+            } else {
+                ActivityStatus::Completed
+            };
+
+            Activity {
+                title: c.category.clone(),
+                duration_minutes: minutes,
+                category: c.category.clone(),
+                status,
+            }
+        })
+        .collect();
+
+    let local = |t: DateTime<Utc>| t.with_timezone(tz).format("%H:%M").to_string();
+    TimeBlock {
+        time_range: format!("{} - {}", local(start), local(end)),
+        planned_activities,
+        actual_activities,
+    }
+}
+
+/// Per-category planned vs actual hours, largest actual first
+fn category_summaries(plans: &[PlannedEvent], actual: &[ActualBlock]) -> Vec<CategorySummary> {
+    let mut hours: BTreeMap<String, (f64, f64)> = BTreeMap::new();
+    for plan in plans {
+        hours.entry(plan.category.clone()).or_default().0 += plan.duration_minutes / 60.0;
+    }
+    for c in actual.iter().flat_map(|block| &block.categories) {
+        hours.entry(c.category.clone()).or_default().1 += c.seconds / 3600.0;
+    }
+
+    let mut summaries: Vec<CategorySummary> = hours
+        .into_iter()
+        .map(|(name, (planned_hours, actual_hours))| CategorySummary {
+            name,
+            planned_hours,
+            actual_hours,
+            variance: actual_hours - planned_hours,
+        })
+        .collect();
+    summaries.sort_by(|a, b| b.actual_hours.total_cmp(&a.actual_hours));
+    summaries
+}
+
+/// Rule-based suggestions derived from the day's numbers
+fn suggestions(reality: &RealityCheck, summary: &DailySummary) -> Vec<Suggestion> {
+    let mut suggestions = Vec::new();
+
+    if summary.total_planned_hours == 0.0 {
+        suggestions.push(Suggestion {
+            title: "Plan your day".into(),
+            description: "Nothing was planned. Put your main tasks on the calendar so actual time can be compared against them.".into(),
+            priority: PriorityLevel::Medium,
+        });
+    } else if reality.planned_vs_actual < 50.0 {
+        suggestions.push(Suggestion {
+            title: "Plan smaller blocks".into(),
+            description: format!(
+                "Only {:.0}% of planned time was spent as planned. Schedule fewer, shorter blocks.",
+                reality.planned_vs_actual
+            ),
+            priority: PriorityLevel::High,
+        });
+    }
+
+    if reality.distractions_detected > 0 && reality.focus_score < 70.0 {
+        suggestions.push(Suggestion {
+            title: "Cut distractions".into(),
+            description: format!(
+                "Distracting activity showed up in {} block(s). Block those sites during focus time.",
+                reality.distractions_detected
+            ),
+            priority: PriorityLevel::High,
+        });
+    }
+
+    if reality.time_overruns > 0 {
+        suggestions.push(Suggestion {
+            title: "Budget more time".into(),
+            description: format!(
+                "{} planned block(s) ran over. Add buffer to similar tasks.",
+                reality.time_overruns
+            ),
+            priority: PriorityLevel::Low,
+        });
+    }
+
+    suggestions
+}
+
+/// Actual activity per timeline block: stored blocks first, ActivityWatch for the rest
+async fn actual_blocks(
+    client: &Client,
+    blocks: &[(DateTime<Utc>, DateTime<Utc>)],
+    categorizer: &Categorizer,
+) -> Result<Vec<ActualBlock>, String> {
+    let (Some(first), Some(last)) = (blocks.first(), blocks.last()) else {
+        return Ok(Vec::new());
+    };
+    let stored = {
+        let conn = database::connection();
+        store::blocks_between(&conn, first.0, last.1).map_err(|e| e.to_string())?
+    };
+
+    let now = Utc::now();
+    let mut actual = Vec::new();
+    for (start, end) in blocks {
+        if let Some(block) = stored
+            .iter()
+            .find(|b| b.block_start == *start && b.block_end == *end)
+        {
+            actual.push(ActualBlock {
+                categories: block.categories.clone(),
+                afk_seconds: block.afk_seconds,
+            });
+            continue;
+        }
+
+        if *start >= now {
+            actual.push(ActualBlock {
+                categories: Vec::new(),
+                afk_seconds: 0.0,
+            });
+            continue;
+        }
+
+        // Not stored yet (current block, or never processed): read ActivityWatch directly
+        match get_active_events(client, *start, (*end).min(now)).await {
+            Ok(active) => actual.push(ActualBlock {
+                categories: categorizer.totals(&active.events),
+                afk_seconds: active.afk_seconds,
+            }),
+            Err(e) => {
+                eprintln!("⚠️ No activity for {} → {}: {}", start, end, e);
+                actual.push(ActualBlock {
+                    categories: Vec::new(),
+                    afk_seconds: 0.0,
+                });
+            }
+        }
+    }
+    Ok(actual)
+}
+
+/// Planned vs actual activity for every block of a day (`YYYY-MM-DD`, today by default)
+#[tauri::command]
+pub async fn get_day_view(date: Option<String>) -> Result<FrontendData, String> {
+    let client = Client::new();
+    let tz = user_timezone();
+    let date = match date {
+        Some(d) => parse_date(&d)?,
+        None => today(&tz),
+    };
+    let (day_start, day_end) = day_range(&tz, date);
+
+    let categorizer = {
+        let conn = database::connection();
+        Categorizer::load(&conn).map_err(|e| e.to_string())?
+    };
+
+    // Plans are optional: without a calendar connection the day still shows actual activity
+    let events = with_access_token(&client, |token| {
+        let client = &client;
+        async move { plan_events(client, &token, day_start, day_end).await }
+    })
+    .await
+    .unwrap_or_else(|e| {
+        eprintln!("⚠️ Could not load planned events: {}", e);
+        Vec::new()
+    });
+    let plans = planned_events(&events, &categorizer, &tz);
+
+    let minutes = block_minutes();
+    let mut blocks = Vec::new();
+    let mut current = day_start;
+    while current < day_end {
+        let end = (current + Duration::minutes(minutes)).min(day_end);
+        blocks.push((current, end));
+        current = end;
+    }
+
+    let actual = actual_blocks(&client, &blocks, &categorizer).await?;
+    let time_blocks: Vec<TimeBlock> = blocks
+        .iter()
+        .zip(&actual)
+        .map(|((start, end), actual)| build_block(*start, *end, &plans, actual, &tz))
+        .collect();
+
+    // --- Daily summary ---
+    let all_actual: Vec<&CategoryTotal> = actual.iter().flat_map(|b| &b.categories).collect();
+    let active_minutes: f64 = all_actual.iter().map(|c| c.seconds / 60.0).sum();
+    let productive_minutes: f64 = all_actual
+        .iter()
+        .filter(|c| c.productivity == Productivity::Productive)
+        .map(|c| c.seconds / 60.0)
+        .sum();
+    let distracting_minutes: f64 = all_actual
+        .iter()
+        .filter(|c| c.productivity == Productivity::Distracting)
+        .map(|c| c.seconds / 60.0)
+        .sum();
+    let planned_minutes: f64 = plans
+        .iter()
+        .map(|p| overlap_minutes((p.start, p.end), (day_start, day_end)))
+        .sum();
+
+    let daily_summary = DailySummary {
+        total_planned_hours: planned_minutes / 60.0,
+        total_actual_hours: active_minutes / 60.0,
+        productivity_score: if active_minutes > 0.0 {
+            productive_minutes / active_minutes * 100.0
+        } else {
+            0.0
+        },
+        main_categories: category_summaries(&plans, &actual),
+    };
+
+    // --- Reality check ---
+    let planned_activities = time_blocks.iter().flat_map(|b| &b.planned_activities);
+    let (matched_planned, total_planned) =
+        planned_activities
+            .clone()
+            .fold((0.0, 0.0), |(done, total), a| {
+                let done_minutes = match a.status {
+                    ActivityStatus::Completed | ActivityStatus::Overrun => a.duration_minutes,
+                    ActivityStatus::Partial => a.duration_minutes / 2.0,
+                    _ => 0.0,
+                };
+                (done + done_minutes, total + a.duration_minutes)
+            });
+
+    let reality_check = RealityCheck {
+        planned_vs_actual: if total_planned > 0.0 {
+            matched_planned / total_planned * 100.0
+        } else {
+            0.0
+        },
+        distractions_detected: actual
+            .iter()
+            .filter(|b| distracted_minutes(&b.categories) > 0.0)
+            .count(),
+        time_overruns: planned_activities
+            .filter(|a| matches!(a.status, ActivityStatus::Overrun))
+            .count(),
+        focus_score: if productive_minutes + distracting_minutes > 0.0 {
+            productive_minutes / (productive_minutes + distracting_minutes) * 100.0
+        } else {
+            0.0
+        },
+        afk_minutes: actual.iter().map(|b| b.afk_seconds / 60.0).sum(),
+    };
+
+    Ok(FrontendData {
+        suggestions: suggestions(&reality_check, &daily_summary),
+        time_blocks,
+        daily_summary,
+        reality_check,
+        date: date.format("%Y-%m-%d").to_string(),
+    })
+}
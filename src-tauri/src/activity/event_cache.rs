@@ -0,0 +1,266 @@
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use chrono_tz::Tz;
+use reqwest::Client;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::activity::{
+    calendar::{get_calendar_events, is_sync_token_expired, list_events, ApiError, CalendarEvent},
+};
+use crate::database;
+use crate::timezone::user_timezone;
+use crate::users::active_user_id;
+
+/// How far back a full sync reaches; older ranges are fetched directly
+const SYNC_WINDOW_DAYS: i64 = 90;
+/// Largest page Google allows for `events.list`
+const PAGE_SIZE: &str = "2500";
+
+struct SyncState {
+    sync_token: String,
+    synced_from: DateTime<Utc>,
+}
+
+/// Fixed-width UTC timestamps so range queries can compare strings
+fn utc_key(t: DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn load_state(
+    conn: &Connection,
+    user_id: i64,
+    calendar_id: &str,
+) -> rusqlite::Result<Option<SyncState>> {
+    conn.query_row(
+        "SELECT sync_token, synced_from FROM calendar_sync_state
+         WHERE user_id = ?1 AND calendar_id = ?2",
+        params![user_id, calendar_id],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+    )
+    .optional()
+    .map(|row| {
+        row.and_then(|(sync_token, synced_from)| {
+            let synced_from = DateTime::parse_from_rfc3339(&synced_from).ok()?;
+            Some(SyncState {
+                sync_token,
+                synced_from: synced_from.with_timezone(&Utc),
+            })
+        })
+    })
+}
+
+/// Apply one sync result to the cache. A full sync replaces everything cached
+/// for the calendar; an incremental one upserts changes and drops cancellations.
+fn apply_sync(
+    conn: &Connection,
+    user_id: i64,
+    calendar_id: &str,
+    events: &[CalendarEvent],
+    state: &SyncState,
+    full: bool,
+    tz: &Tz,
+) -> rusqlite::Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    let now = Utc::now().to_rfc3339();
+
+    if full {
+        tx.execute(
+            "DELETE FROM calendar_event_cache WHERE user_id = ?1 AND calendar_id = ?2",
+            params![user_id, calendar_id],
+        )?;
+    }
+
+    for ev in events {
+        let Some((start, end)) = ev.time_range(tz).filter(|_| !ev.is_cancelled()) else {
+            tx.execute(
+                "DELETE FROM calendar_event_cache
+                 WHERE user_id = ?1 AND calendar_id = ?2 AND event_id = ?3",
+                params![user_id, calendar_id, ev.id],
+            )?;
+            continue;
+        };
+
+        let data = serde_json::to_string(ev)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        tx.execute(
+            "INSERT INTO calendar_event_cache (
+                user_id, calendar_id, event_id, start_time, end_time, data, updated_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            ON CONFLICT(user_id, calendar_id, event_id) DO UPDATE SET
+                start_time = excluded.start_time,
+                end_time = excluded.end_time,
+                data = excluded.data,
+                updated_at = excluded.updated_at",
+            params![
+                user_id,
+                calendar_id,
+                ev.id,
+                utc_key(start),
+                utc_key(end),
+                data,
+                now
+            ],
+        )?;
+    }
+
+    tx.execute(
+        "INSERT INTO calendar_sync_state (user_id, calendar_id, sync_token, synced_from, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(user_id, calendar_id) DO UPDATE SET
+            sync_token = excluded.sync_token,
+            synced_from = excluded.synced_from,
+            updated_at = excluded.updated_at",
+        params![
+            user_id,
+            calendar_id,
+            state.sync_token,
+            state.synced_from.to_rfc3339(),
+            now
+        ],
+    )?;
+    tx.commit()
+}
+
+/// Forget the cached events and sync tokens of an account; its next read does a full sync
+pub fn reset(conn: &Connection, user_id: i64) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM calendar_event_cache WHERE user_id = ?1", params![user_id])?;
+    conn.execute("DELETE FROM calendar_sync_state WHERE user_id = ?1", params![user_id])?;
+    Ok(())
+}
+
+fn cached_events(
+    conn: &Connection,
+    user_id: i64,
+    calendar_id: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> rusqlite::Result<Vec<CalendarEvent>> {
+    let mut stmt = conn.prepare(
+        "SELECT data FROM calendar_event_cache
+         WHERE user_id = ?1 AND calendar_id = ?2
+           AND start_time < ?4 AND (end_time > ?3 OR start_time >= ?3)
+         ORDER BY start_time",
+    )?;
+    let rows = stmt.query_map(
+        params![user_id, calendar_id, utc_key(start), utc_key(end)],
+        |row| row.get::<_, String>(0),
+    )?;
+
+    let mut events = Vec::new();
+    for data in rows {
+        match serde_json::from_str(&data?) {
+            Ok(ev) => events.push(ev),
+            Err(e) => eprintln!("⚠️ Skipping unreadable cached event: {}", e),
+        }
+    }
+    Ok(events)
+}
+
+/// Bring the cache of one calendar up to date: incrementally when a sync
+/// token is stored, otherwise (or when Google expired it) with a full sync.
+/// Returns the start of the cached window.
+pub async fn sync_calendar(
+    client: &Client,
+    token: &str,
+    calendar_id: &str,
+) -> Result<DateTime<Utc>, ApiError> {
+    let tz = user_timezone();
+    let stored = {
+        let conn = database::connection();
+        load_state(&conn, active_user_id(&conn), calendar_id).map_err(|e| e.to_string())?
+    };
+
+    if let Some(state) = stored {
+        let query = [
+            ("syncToken", state.sync_token.clone()),
+            ("singleEvents", "true".to_string()),
+            ("maxResults", PAGE_SIZE.to_string()),
+        ];
+        match list_events(client, token, calendar_id, &query).await {
+            Ok(list) => {
+                let next = SyncState {
+                    sync_token: list.next_sync_token.unwrap_or(state.sync_token),
+                    synced_from: state.synced_from,
+                };
+                let conn = database::connection();
+                apply_sync(
+                    &conn,
+                    active_user_id(&conn),
+                    calendar_id,
+                    &list.events,
+                    &next,
+                    false,
+                    &tz,
+                )
+                .map_err(|e| e.to_string())?;
+                return Ok(next.synced_from);
+            }
+            Err(e) if is_sync_token_expired(&e) => {
+                println!("🔄 Sync token for {} expired, resyncing", calendar_id);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    let synced_from = Utc::now() - Duration::days(SYNC_WINDOW_DAYS);
+    let query = [
+        ("timeMin", synced_from.to_rfc3339()),
+        ("singleEvents", "true".to_string()),
+        ("maxResults", PAGE_SIZE.to_string()),
+    ];
+    let list = list_events(client, token, calendar_id, &query).await?;
+    let Some(sync_token) = list.next_sync_token else {
+        return Err(format!("No sync token returned for calendar {}", calendar_id).into());
+    };
+
+    let state = SyncState {
+        sync_token,
+        synced_from,
+    };
+    let conn = database::connection();
+    apply_sync(
+        &conn,
+        active_user_id(&conn),
+        calendar_id,
+        &list.events,
+        &state,
+        true,
+        &tz,
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(synced_from)
+}
+
+/// Cached events of one calendar overlapping `[start, end)`, or `None` when
+/// the cache does not cover the range
+pub fn stored_events(
+    conn: &Connection,
+    calendar_id: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> rusqlite::Result<Option<Vec<CalendarEvent>>> {
+    match load_state(conn, active_user_id(conn), calendar_id)? {
+        Some(state) if start >= state.synced_from => {
+            cached_events(conn, active_user_id(conn), calendar_id, start, end).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Events of one calendar overlapping `[start, end)`. Served from the synced
+/// cache; ranges older than the sync window go straight to the API.
+pub async fn events_between(
+    client: &Client,
+    token: &str,
+    calendar_id: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<CalendarEvent>, ApiError> {
+    let synced_from = sync_calendar(client, token, calendar_id).await?;
+    if start < synced_from {
+        return get_calendar_events(client, token, calendar_id, start, end).await;
+    }
+
+    let conn = database::connection();
+    cached_events(&conn, active_user_id(&conn), calendar_id, start, end)
+        .map_err(|e| e.to_string().into())
+}
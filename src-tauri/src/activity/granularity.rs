@@ -0,0 +1,72 @@
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+
+use crate::database::{self, settings};
+use crate::timezone::{start_of_day, user_timezone};
+
+const BLOCK_MINUTES_SETTING: &str = "block_minutes";
+pub const DEFAULT_BLOCK_MINUTES: i64 = 60;
+/// Supported block sizes; all of them divide a day evenly
+pub const ALLOWED_BLOCK_MINUTES: [i64; 4] = [15, 30, 60, 90];
+
+/// Configured summary block size in minutes
+pub fn block_minutes() -> i64 {
+    let conn = database::connection();
+    settings::get(&conn, BLOCK_MINUTES_SETTING)
+        .ok()
+        .flatten()
+        .and_then(|s| s.parse().ok())
+        .filter(|m| ALLOWED_BLOCK_MINUTES.contains(m))
+        .unwrap_or(DEFAULT_BLOCK_MINUTES)
+}
+
+/// Start of the block containing `t`. Blocks are counted in elapsed time from
+/// midnight in the user's timezone, so they stay contiguous across DST changes.
+pub fn block_start(t: DateTime<Utc>, minutes: i64) -> DateTime<Utc> {
+    block_start_in(&user_timezone(), t, minutes)
+}
+
+/// `block_start` in a given timezone
+pub fn block_start_in(tz: &Tz, t: DateTime<Utc>, minutes: i64) -> DateTime<Utc> {
+    let midnight = start_of_day(tz, t.with_timezone(tz).date_naive());
+    let elapsed = (t - midnight).num_minutes();
+
+    midnight + Duration::minutes(elapsed / minutes * minutes)
+}
+
+/// Consecutive blocks from `start` to `end`, both on the grid. Each block ends
+/// on the next grid point rather than a fixed length later, so blocks restart
+/// at midnight on days that aren't a whole number of blocks long (DST days).
+pub fn blocks_in(
+    tz: &Tz,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    minutes: i64,
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let mut blocks = Vec::new();
+    let mut current = start;
+    while current < end {
+        let next = block_start_in(tz, current + Duration::minutes(minutes), minutes).min(end);
+        blocks.push((current, next));
+        current = next;
+    }
+    blocks
+}
+
+#[tauri::command]
+pub fn get_block_minutes() -> i64 {
+    block_minutes()
+}
+
+/// Change the summary block size (15, 30, 60 or 90 minutes)
+#[tauri::command]
+pub fn set_block_minutes(minutes: i64) -> Result<(), String> {
+    if !ALLOWED_BLOCK_MINUTES.contains(&minutes) {
+        return Err(format!(
+            "Block size must be one of {:?} minutes",
+            ALLOWED_BLOCK_MINUTES
+        ));
+    }
+    let conn = database::connection();
+    settings::set(&conn, BLOCK_MINUTES_SETTING, &minutes.to_string()).map_err(|e| e.to_string())
+}
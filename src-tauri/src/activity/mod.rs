@@ -2,8 +2,16 @@ pub mod token;
 pub mod activitywatch;
 pub mod summarize;
 pub mod calendar;
+pub mod calendars;
+pub mod event_cache;
 pub mod commands;
 pub mod processor;
 pub mod models;
+pub mod store;
+pub mod outbox;
+pub mod scheduler;
+pub mod granularity;
+pub mod categorize;
+pub mod day_view;
 
 pub use commands::*;
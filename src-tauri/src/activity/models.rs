@@ -48,6 +48,7 @@ pub struct RealityCheck {
     pub distractions_detected: usize,
     pub time_overruns: usize,
     pub focus_score: f64,
+    pub afk_minutes: f64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -0,0 +1,308 @@
+use chrono::{DateTime, Duration, Utc};
+use once_cell::sync::Lazy;
+use reqwest::{Client, StatusCode};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use crate::activity::{
+    calendar::upsert_calendar_event,
+    calendars::{ensure_activity_calendar, forget_activity_calendar},
+    store::{self, ActivityBlock},
+    token::with_access_token,
+};
+use crate::database;
+use crate::users::active_user_id;
+
+/// First retry delay; doubles on every failed attempt
+const BASE_BACKOFF_SECS: i64 = 60;
+/// Never wait longer than this between attempts
+const MAX_BACKOFF_SECS: i64 = 6 * 3600;
+/// How often the background worker looks for due entries
+const WORKER_INTERVAL_SECS: u64 = 60;
+
+#[derive(Debug, Serialize)]
+pub struct OutboxEntry {
+    pub id: i64,
+    pub activity_block_id: i64,
+    pub block_start: String,
+    pub block_end: String,
+    pub title: String,
+    pub attempts: i64,
+    pub next_attempt_at: String,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QueueStatus {
+    pub pending: usize,
+    pub failing: usize,
+    pub next_attempt_at: Option<String>,
+    pub entries: Vec<OutboxEntry>,
+}
+
+/// Blocks being pushed right now. The worker and the inline push after
+/// processing a block both run in this process; without a claim they could
+/// both find no event for a block and create two.
+static IN_FLIGHT: Lazy<Mutex<HashSet<i64>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Claim on pushing one block, released when dropped
+struct Claim(i64);
+
+impl Claim {
+    /// `None` while another push of the block is in flight
+    fn take(block_id: i64) -> Result<Option<Claim>, String> {
+        let mut in_flight = IN_FLIGHT.lock().map_err(|e| e.to_string())?;
+        Ok(in_flight.insert(block_id).then_some(Claim(block_id)))
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        if let Ok(mut in_flight) = IN_FLIGHT.lock() {
+            in_flight.remove(&self.0);
+        }
+    }
+}
+
+/// Delay before the next attempt after `attempts` failures
+fn backoff(attempts: i64) -> Duration {
+    let exp = attempts.clamp(0, 20) as u32;
+    let secs = BASE_BACKOFF_SECS.saturating_mul(2i64.saturating_pow(exp));
+    Duration::seconds(secs.min(MAX_BACKOFF_SECS))
+}
+
+/// Queue a block for pushing; a block already queued keeps its retry state
+pub fn enqueue(conn: &Connection, block_id: i64) -> rusqlite::Result<()> {
+    let now = Utc::now().to_rfc3339();
+    conn.execute(
+        "INSERT OR IGNORE INTO calendar_outbox (
+            activity_block_id, attempts, next_attempt_at, created_at, updated_at
+        ) VALUES (?1, 0, ?2, ?2, ?2)",
+        params![block_id, now],
+    )?;
+    Ok(())
+}
+
+fn record_success(conn: &Connection, block_id: i64, event_id: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM calendar_outbox WHERE activity_block_id = ?1",
+        params![block_id],
+    )?;
+    store::mark_synced(conn, block_id, Some(event_id))
+}
+
+/// Back off the entry after a failed push; returns the next attempt, or `None`
+/// when the entry is gone because another push of the block succeeded
+fn record_failure(
+    conn: &Connection,
+    block_id: i64,
+    error: &str,
+) -> rusqlite::Result<Option<DateTime<Utc>>> {
+    let Some(attempts) = conn
+        .query_row(
+            "SELECT attempts FROM calendar_outbox WHERE activity_block_id = ?1",
+            params![block_id],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?
+    else {
+        return Ok(None);
+    };
+    let now = Utc::now();
+    let next_attempt = now + backoff(attempts);
+
+    conn.execute(
+        "UPDATE calendar_outbox
+         SET attempts = attempts + 1, next_attempt_at = ?1, last_error = ?2, updated_at = ?3
+         WHERE activity_block_id = ?4",
+        params![next_attempt.to_rfc3339(), error, now.to_rfc3339(), block_id],
+    )?;
+    store::mark_failed(conn, block_id, error)?;
+    Ok(Some(next_attempt))
+}
+
+/// Try to push a queued block right away. On failure the entry stays queued
+/// with its backoff updated and the error is returned. A block already being
+/// pushed is left to that push.
+pub async fn push_block(client: &Client, block: &ActivityBlock) -> Result<(), String> {
+    let Some(_claim) = Claim::take(block.id)? else {
+        return Err("Calendar push already in progress".into());
+    };
+    let description = block.description();
+    let pushed = with_access_token(client, |token| {
+        let (title, description) = (&block.title, &description);
+        async move {
+            let calendar_id = ensure_activity_calendar(client, &token).await?;
+            let upsert = |calendar_id: String, event_id: Option<String>| {
+                let token = token.clone();
+                async move {
+                    upsert_calendar_event(
+                        client,
+                        &token,
+                        &calendar_id,
+                        title,
+                        description,
+                        block.block_start,
+                        block.block_end,
+                        event_id.as_deref(),
+                    )
+                    .await
+                }
+            };
+            match upsert(calendar_id.clone(), block.calendar_event_id.clone()).await {
+                // The activity calendar was deleted: write to a new one instead
+                Err(e) if e.is(StatusCode::NOT_FOUND) => {
+                    eprintln!("⚠️ Activity calendar {} is gone, replacing it", calendar_id);
+                    {
+                        let conn = database::connection();
+                        forget_activity_calendar(&conn, &calendar_id)
+                            .map_err(|e| e.to_string())?;
+                    }
+                    let calendar_id = ensure_activity_calendar(client, &token).await?;
+                    upsert(calendar_id, None).await
+                }
+                result => result,
+            }
+        }
+    })
+    .await;
+
+    let conn = database::connection();
+    match pushed.map_err(String::from) {
+        Ok(event_id) => record_success(&conn, block.id, &event_id).map_err(|e| e.to_string()),
+        Err(e) => {
+            match record_failure(&conn, block.id, &e).map_err(|e| e.to_string())? {
+                Some(next) => eprintln!(
+                    "⚠️ Calendar push for {} → {} failed, retrying at {}: {}",
+                    block.block_start, block.block_end, next, e
+                ),
+                None => eprintln!(
+                    "⚠️ Calendar push for {} → {} failed: {}",
+                    block.block_start, block.block_end, e
+                ),
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Queued blocks of the active user whose next attempt is due. Other users'
+/// blocks wait until their account is active again, since only its token can
+/// write to their calendar.
+fn due_blocks(conn: &Connection) -> rusqlite::Result<Vec<ActivityBlock>> {
+    let mut stmt = conn.prepare(
+        "SELECT o.activity_block_id FROM calendar_outbox o
+         LEFT JOIN activity_blocks b ON b.id = o.activity_block_id
+         WHERE o.next_attempt_at <= ?1 AND (b.id IS NULL OR b.user_id = ?2)
+         ORDER BY o.next_attempt_at",
+    )?;
+    let ids = stmt
+        .query_map(
+            params![Utc::now().to_rfc3339(), active_user_id(conn)],
+            |row| row.get::<_, i64>(0),
+        )?
+        .collect::<rusqlite::Result<Vec<i64>>>()?;
+
+    let mut blocks = Vec::new();
+    for id in ids {
+        match store::get_block(conn, id)? {
+            Some(block) => blocks.push(block),
+            // Block was deleted, nothing left to push
+            None => {
+                conn.execute(
+                    "DELETE FROM calendar_outbox WHERE activity_block_id = ?1",
+                    params![id],
+                )?;
+            }
+        }
+    }
+    Ok(blocks)
+}
+
+/// Push every due entry once; returns how many were pushed successfully
+pub async fn drain(client: &Client) -> Result<usize, String> {
+    let blocks = {
+        let conn = database::connection();
+        due_blocks(&conn).map_err(|e| e.to_string())?
+    };
+
+    let mut pushed = 0;
+    for block in &blocks {
+        if push_block(client, block).await.is_ok() {
+            pushed += 1;
+        }
+    }
+    Ok(pushed)
+}
+
+/// Background loop draining the outbox; spawned once at startup
+pub async fn run_worker() {
+    use tokio::time::{interval, Duration};
+
+    let client = Client::new();
+    let mut ticker = interval(Duration::from_secs(WORKER_INTERVAL_SECS));
+    loop {
+        ticker.tick().await;
+        match drain(&client).await {
+            Ok(0) => {}
+            Ok(n) => println!("✅ Pushed {} queued calendar event(s)", n),
+            Err(e) => eprintln!("Outbox worker error: {}", e),
+        }
+    }
+}
+
+/// Pending calendar writes and their retry state
+#[tauri::command]
+pub fn get_sync_queue_status() -> Result<QueueStatus, String> {
+    let conn = database::connection();
+    let mut stmt = conn
+        .prepare(
+            "SELECT o.id, o.activity_block_id, b.block_start, b.block_end, b.title,
+                    o.attempts, o.next_attempt_at, o.last_error
+             FROM calendar_outbox o
+             JOIN activity_blocks b ON b.id = o.activity_block_id
+             WHERE b.user_id = ?1
+             ORDER BY o.next_attempt_at",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let entries = stmt
+        .query_map(params![active_user_id(&conn)], |row| {
+            Ok(OutboxEntry {
+                id: row.get(0)?,
+                activity_block_id: row.get(1)?,
+                block_start: row.get(2)?,
+                block_end: row.get(3)?,
+                title: row.get(4)?,
+                attempts: row.get(5)?,
+                next_attempt_at: row.get(6)?,
+                last_error: row.get(7)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(QueueStatus {
+        pending: entries.len(),
+        failing: entries.iter().filter(|e| e.attempts > 0).count(),
+        next_attempt_at: entries.first().map(|e| e.next_attempt_at.clone()),
+        entries,
+    })
+}
+
+/// Retry every queued write now, ignoring backoff
+#[tauri::command]
+pub async fn retry_sync_queue() -> Result<usize, String> {
+    {
+        let conn = database::connection();
+        conn.execute(
+            "UPDATE calendar_outbox SET next_attempt_at = ?1",
+            params![Utc::now().to_rfc3339()],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    drain(&Client::new()).await
+}
@@ -1,15 +1,24 @@
-use chrono::{DateTime, Duration, Timelike, Utc};
+use chrono::{DateTime, Duration, NaiveDate, Timelike, Utc};
+use chrono_tz::Tz;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
-// Assuming this struct exists in your codebase
-use crate::auth::get_latest_token;
-use crate::daily_report::get_calendar_events;
+use crate::activity::calendar::CalendarEvent;
+use crate::activity::calendars::load_plan_events;
+use crate::activity::granularity::block_minutes;
+use crate::activity::store::{self, ActivityBlock};
+use crate::database;
+use crate::timezone::{date_span, day_range, user_timezone};
 
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// One block of the day's timeline (an hour unless another block size is configured)
 #[derive(Serialize, Deserialize, Debug)]
 pub struct HourBlock {
     pub hour_24: u8,
+    /// Minutes since midnight, end exclusive
+    pub start_minute: u16,
+    pub end_minute: u16,
     pub label: String,
     pub start: String,
     pub end: String,
@@ -19,123 +28,238 @@ pub struct HourBlock {
 pub struct Batch {
     pub start_hour: u8,
     pub end_hour: u8,
+    /// Minutes since midnight, end exclusive
+    pub start_minute: u16,
+    pub end_minute: u16,
     pub label: String,
     pub is_event: bool,
-    pub events: Vec<Value>, // Add this field
+    pub events: Vec<CalendarEvent>,
+    /// Activity recorded during the batch (empty for days still ahead)
+    pub actual: Vec<ActivityBlock>,
 }
 
-#[tauri::command]
-pub async fn fetch_batches() -> Result<Vec<Batch>, String> {
-    let client = Client::new();
-
-    // 1) Fetch latest token from DB
-    let token_data = get_latest_token()?;
-    let token = token_data.access_token;
-
-    // 2) Build today's 24-hour blocks
-    let hours = (0..24)
-        .map(|h| {
-            let hour_12 = if h % 12 == 0 { 12 } else { h % 12 };
-            let suffix = if h < 12 { "AM" } else { "PM" };
-            let label = format!("{}:00 {} - {}:59 {}", hour_12, suffix, hour_12, suffix);
+/// The timeline of one day in a multi-day view
+#[derive(Serialize, Debug)]
+pub struct DayBatches {
+    /// `YYYY-MM-DD`
+    pub date: String,
+    pub batches: Vec<Batch>,
+    /// All-day events (birthdays, out of office), listed apart from the batches
+    pub all_day: Vec<CalendarEvent>,
+}
+
+/// 12-hour clock label, e.g. "9:30 AM"
+fn clock_label(minute_of_day: u16) -> String {
+    let h = minute_of_day / 60;
+    let hour_12 = if h % 12 == 0 { 12 } else { h % 12 };
+    let suffix = if h < 12 { "AM" } else { "PM" };
+    format!("{}:{:02} {}", hour_12, minute_of_day % 60, suffix)
+}
+
+/// The day split into blocks of `block_minutes`
+pub fn day_blocks(block_minutes: u16) -> Vec<HourBlock> {
+    (0..MINUTES_PER_DAY)
+        .step_by(block_minutes as usize)
+        .map(|start| {
+            let last = start + block_minutes - 1;
             HourBlock {
-                hour_24: h as u8,
-                label,
-                start: format!("{:02}:00", h),
-                end: format!("{:02}:59", h),
+                hour_24: (start / 60) as u8,
+                start_minute: start,
+                end_minute: start + block_minutes,
+                label: format!("{} - {}", clock_label(start), clock_label(last)),
+                start: format!("{:02}:{:02}", start / 60, start % 60),
+                end: format!("{:02}:{:02}", last / 60, last % 60),
             }
         })
-        .collect::<Vec<HourBlock>>();
-
-    // 3) Today range
-    let local_now = chrono::Local::now();
-
-    println!("Current UTC time: {}", local_now);
-    println!(
-        "Current local time: {}",
-        local_now.with_timezone(&chrono::Local)
-    );
-    let start_of_day = local_now
-        .date_naive()
-        .and_hms_opt(0, 0, 0)
-        .unwrap()
-        .and_utc();
-    println!("Start of day UTC: {}", start_of_day);
-    let end_of_day = start_of_day + Duration::hours(24);
-
-    // 4) Fetch events
-    let events = get_calendar_events(&client, &token, start_of_day, end_of_day).await?;
-    // 5) Make batches
-    Ok(make_batches(hours, events))
+        .collect()
 }
 
-/// Existing batching function
-/// Modified batching function:
-/// - Events: each hour is its own batch (1-hour blocks)
-/// Modified batching function:
-/// - Events: each hour is its own batch (1-hour blocks)
-/// - Multi-hour events appear in EACH hour they span
-/// - Free time: batch all consecutive free hours together
-pub fn make_batches(hours: Vec<HourBlock>, events: Vec<Value>) -> Vec<Batch> {
-    // Map each hour to all events that occur during that hour
-    let mut hours_with_events: std::collections::HashMap<u8, Vec<Value>> = std::collections::HashMap::new();
-    
-    for ev in &events {
-        if let Some(start_str) = ev["start"]["dateTime"].as_str() {
-            if let Some(end_str) = ev["end"]["dateTime"].as_str() {
-                if let (Ok(start_dt), Ok(end_dt)) = (
-                    DateTime::parse_from_rfc3339(start_str),
-                    DateTime::parse_from_rfc3339(end_str)
-                ) {
-                    let start_hour = start_dt.hour() as u8;
-                    let end_hour = end_dt.hour() as u8;
-                    let end_minute = end_dt.minute();
-                    
-                    // If event ends exactly on the hour (minute=0), don't include that hour
-                    let actual_end_hour = if end_minute == 0 && end_hour > start_hour {
-                        end_hour - 1
-                    } else {
-                        end_hour
-                    };
-                    
-                    for hour in start_hour..=actual_end_hour {
-                        hours_with_events
-                            .entry(hour)
-                            .or_insert_with(Vec::new)
-                            .push(ev.clone());
-                    }
-                }
+/// Timelines of every day from `first` to `last`. Plans come from the
+/// calendar (from the local cache for days already over); recorded activity
+/// from stored blocks, so days still ahead show plans only.
+async fn span_batches(
+    client: &Client,
+    tz: &Tz,
+    first: NaiveDate,
+    last: NaiveDate,
+) -> Result<Vec<DayBatches>, String> {
+    let minutes = block_minutes() as u16;
+    let (span_start, _) = day_range(tz, first);
+    let (_, span_end) = day_range(tz, last);
+
+    let events = load_plan_events(client, span_start, span_end).await?;
+    let actual = {
+        let conn = database::connection();
+        store::blocks_between(&conn, span_start, span_end).map_err(|e| e.to_string())?
+    };
+
+    let mut days = Vec::new();
+    let mut date = first;
+    while date <= last {
+        let day = day_range(tz, date);
+        let mut batches = make_batches(day_blocks(minutes), events.clone(), day, tz);
+        attach_actual(&mut batches, &actual, day, tz);
+        days.push(DayBatches {
+            date: date.format("%Y-%m-%d").to_string(),
+            batches,
+            all_day: all_day_events(&events, day, tz),
+        });
+        date += Duration::days(1);
+    }
+    Ok(days)
+}
+
+/// Timeline of one day (`YYYY-MM-DD`, today by default)
+#[tauri::command]
+pub async fn fetch_batches(date: Option<String>) -> Result<DayBatches, String> {
+    let tz = user_timezone();
+    let (day, _) = date_span(&tz, date, None)?;
+    let mut days = span_batches(&Client::new(), &tz, day, day).await?;
+    days.pop().ok_or_else(|| "No day to show".to_string())
+}
+
+/// Timelines of every day from `start_date` to `end_date` (inclusive, `YYYY-MM-DD`)
+#[tauri::command]
+pub async fn fetch_batches_range(
+    start_date: String,
+    end_date: String,
+) -> Result<Vec<DayBatches>, String> {
+    let tz = user_timezone();
+    let (first, last) = date_span(&tz, Some(start_date), Some(end_date))?;
+    span_batches(&Client::new(), &tz, first, last).await
+}
+
+/// Minutes since midnight on the wall clock of `tz`, clamped to `day`
+fn wall_minute(t: DateTime<Utc>, day: (DateTime<Utc>, DateTime<Utc>), tz: &Tz) -> u16 {
+    if t <= day.0 {
+        0
+    } else if t >= day.1 {
+        MINUTES_PER_DAY
+    } else {
+        let local = t.with_timezone(tz);
+        (local.hour() * 60 + local.minute()) as u16
+    }
+}
+
+/// Put every stored block of `day` into the batch its start falls in
+fn attach_actual(
+    batches: &mut [Batch],
+    actual: &[ActivityBlock],
+    day: (DateTime<Utc>, DateTime<Utc>),
+    tz: &Tz,
+) {
+    for block in actual {
+        if block.block_start < day.0 || block.block_start >= day.1 {
+            continue;
+        }
+        let minute = wall_minute(block.block_start, day, tz);
+        if let Some(batch) = batches
+            .iter_mut()
+            .find(|b| b.start_minute <= minute && minute < b.end_minute)
+        {
+            batch.actual.push(block.clone());
+        }
+    }
+}
+
+/// All-day events covering any part of `day`; `make_batches` leaves them out
+pub fn all_day_events(
+    events: &[CalendarEvent],
+    day: (DateTime<Utc>, DateTime<Utc>),
+    tz: &Tz,
+) -> Vec<CalendarEvent> {
+    events
+        .iter()
+        .filter(|ev| ev.is_all_day())
+        .filter(|ev| ev.time_range(tz).is_some_and(|(start, end)| start < day.1 && end > day.0))
+        .cloned()
+        .collect()
+}
+
+/// Batch spanning `blocks[first..=last]`
+fn make_batch(
+    first: &HourBlock,
+    last: &HourBlock,
+    is_event: bool,
+    events: Vec<CalendarEvent>,
+) -> Batch {
+    let kind = if is_event { "Event" } else { "Free" };
+    Batch {
+        start_hour: first.hour_24,
+        end_hour: ((last.end_minute - 1) / 60) as u8,
+        start_minute: first.start_minute,
+        end_minute: last.end_minute,
+        label: format!("{}: {} - {}", kind, first.start, last.end),
+        is_event,
+        events,
+        actual: Vec::new(),
+    }
+}
+
+/// Batching function:
+/// - Events: each block is its own batch, unless the same single event continues
+/// - Multi-block events appear in EACH block they span
+/// - Free time: batch all consecutive free blocks together
+///
+/// Events are placed on the wall clock of `tz` within `day` (`[start, end)` in
+/// UTC, 23 or 25 hours long on DST changes). Events that began the day before
+/// or run past midnight cover the part of the day they overlap. All-day events
+/// (birthdays, out of office) aren't placed, since they would fill every block;
+/// `all_day_events` lists them instead.
+pub fn make_batches(
+    blocks: Vec<HourBlock>,
+    events: Vec<CalendarEvent>,
+    day: (DateTime<Utc>, DateTime<Utc>),
+    tz: &Tz,
+) -> Vec<Batch> {
+    let (day_start, day_end) = day;
+    let minute_of_day = |t: DateTime<Utc>| wall_minute(t, day, tz);
+
+    // Map each block to all events that occur during that block
+    let mut blocks_with_events: std::collections::HashMap<usize, Vec<CalendarEvent>> =
+        std::collections::HashMap::new();
+
+    for ev in events.iter().filter(|ev| !ev.is_all_day()) {
+        let Some((start, end)) = ev.time_range(tz) else {
+            continue;
+        };
+        if end < day_start || start >= day_end {
+            continue;
+        }
+        let start_minute = minute_of_day(start);
+        // The repeated hour of a DST fall-back can put the end before the start
+        let end_minute = minute_of_day(end).max(start_minute);
+
+        for (i, block) in blocks.iter().enumerate() {
+            let overlaps = block.start_minute < end_minute && block.end_minute > start_minute;
+            // Zero-length events still show up in the block they start in
+            let instant = start == end
+                && (block.start_minute..block.end_minute).contains(&start_minute);
+            if overlaps || instant {
+                blocks_with_events.entry(i).or_default().push(ev.clone());
             }
         }
     }
 
     let mut batches = Vec::new();
-    let mut free_batch_start: Option<u8> = None;
-    let mut event_batch_start: Option<u8> = None;
-    let mut current_events: Vec<Value> = vec![];
+    let mut free_batch_start: Option<usize> = None;
+    let mut event_batch_start: Option<usize> = None;
+    let mut current_events: Vec<CalendarEvent> = vec![];
 
-    for h in &hours {
-        let hour_events = hours_with_events.get(&h.hour_24).cloned().unwrap_or_default();
-        let has_event = !hour_events.is_empty();
+    for (i, _) in blocks.iter().enumerate() {
+        let block_events = blocks_with_events.get(&i).cloned().unwrap_or_default();
+        let has_event = !block_events.is_empty();
 
         if has_event {
             // Close any open free batch
-            if let Some(start) = free_batch_start {
-                let prev_hour = if h.hour_24 > 0 { h.hour_24 - 1 } else { 0 };
-                batches.push(Batch {
-                    start_hour: start,
-                    end_hour: prev_hour,
-                    label: format!("Free: {} - {}", start, prev_hour),
-                    is_event: false,
-                    events: vec![],
-                });
-                free_batch_start = None;
+            if let Some(start) = free_batch_start.take() {
+                batches.push(make_batch(&blocks[start], &blocks[i - 1], false, vec![]));
             }
 
             // Check if we can continue batching (same single event)
-            let can_batch = hour_events.len() == 1 
-                && current_events.len() == 1 
-                && events_are_same(&hour_events[0], &current_events[0]);
+            let can_batch = block_events.len() == 1
+                && current_events.len() == 1
+                && events_are_same(&block_events[0], &current_events[0]);
 
             if can_batch {
                 // Continue batching the same single event
@@ -143,75 +267,173 @@ pub fn make_batches(hours: Vec<HourBlock>, events: Vec<Value>) -> Vec<Batch> {
             } else {
                 // Close previous event batch if exists
                 if let Some(start) = event_batch_start {
-                    let prev_hour = if h.hour_24 > 0 { h.hour_24 - 1 } else { 0 };
-                    batches.push(Batch {
-                        start_hour: start,
-                        end_hour: prev_hour,
-                        label: format!("Event: {} - {}", start, prev_hour),
-                        is_event: true,
-                        events: current_events.clone(),
-                    });
+                    batches.push(make_batch(&blocks[start], &blocks[i - 1], true, current_events.clone()));
                 }
-                
+
                 // Start new event batch
-                event_batch_start = Some(h.hour_24);
-                current_events = hour_events;
+                event_batch_start = Some(i);
+                current_events = block_events;
             }
         } else {
             // Close any open event batch
-            if let Some(start) = event_batch_start {
-                let prev_hour = if h.hour_24 > 0 { h.hour_24 - 1 } else { 0 };
-                batches.push(Batch {
-                    start_hour: start,
-                    end_hour: prev_hour,
-                    label: format!("Event: {} - {}", start, prev_hour),
-                    is_event: true,
-                    events: current_events.clone(),
-                });
-                event_batch_start = None;
+            if let Some(start) = event_batch_start.take() {
+                batches.push(make_batch(&blocks[start], &blocks[i - 1], true, current_events.clone()));
                 current_events = vec![];
             }
 
             // Start or continue free time batch
             if free_batch_start.is_none() {
-                free_batch_start = Some(h.hour_24);
+                free_batch_start = Some(i);
             }
         }
     }
 
+    let Some(last) = blocks.last() else {
+        return batches;
+    };
+
     // Close any remaining event batch
     if let Some(start) = event_batch_start {
-        batches.push(Batch {
-            start_hour: start,
-            end_hour: 23,
-            label: format!("Event: {} - 23", start),
-            is_event: true,
-            events: current_events,
-        });
+        batches.push(make_batch(&blocks[start], last, true, current_events));
     }
 
     // Close any remaining free batch
     if let Some(start) = free_batch_start {
-        batches.push(Batch {
-            start_hour: start,
-            end_hour: 23,
-            label: format!("Free: {} - 23", start),
-            is_event: false,
-            events: vec![],
-        });
+        batches.push(make_batch(&blocks[start], last, false, vec![]));
     }
 
     batches
 }
 
 /// Helper function to check if two events are the same
-fn events_are_same(ev1: &Value, ev2: &Value) -> bool {
-    // Compare by ID if available, otherwise by summary and time
-    if let (Some(id1), Some(id2)) = (ev1.get("id"), ev2.get("id")) {
-        return id1 == id2;
-    }
-    
-    ev1.get("summary") == ev2.get("summary") 
-        && ev1.get("start") == ev2.get("start")
-        && ev1.get("end") == ev2.get("end")
-}
\ No newline at end of file
+fn events_are_same(ev1: &CalendarEvent, ev2: &CalendarEvent) -> bool {
+    // Instances of a recurring event have ids of their own
+    ev1.id == ev2.id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timezone::parse_date;
+    use serde_json::json;
+
+    fn berlin() -> Tz {
+        "Europe/Berlin".parse().unwrap()
+    }
+
+    fn timed_event(id: &str, start: &str, end: &str) -> CalendarEvent {
+        serde_json::from_value(json!({
+            "id": id,
+            "start": { "dateTime": start },
+            "end": { "dateTime": end },
+        }))
+        .unwrap()
+    }
+
+    fn all_day_event(id: &str, date: &str, next_date: &str) -> CalendarEvent {
+        serde_json::from_value(json!({
+            "id": id,
+            "start": { "date": date },
+            "end": { "date": next_date },
+        }))
+        .unwrap()
+    }
+
+    fn batches_on(date: &str, events: Vec<CalendarEvent>) -> Vec<Batch> {
+        let tz = berlin();
+        let day = day_range(&tz, parse_date(date).unwrap());
+        make_batches(day_blocks(60), events, day, &tz)
+    }
+
+    fn spans(batches: &[Batch]) -> Vec<(bool, u16, u16)> {
+        batches
+            .iter()
+            .map(|b| (b.is_event, b.start_minute, b.end_minute))
+            .collect()
+    }
+
+    #[test]
+    fn free_day_is_one_batch() {
+        let batches = batches_on("2024-05-02", vec![]);
+        assert_eq!(spans(&batches), vec![(false, 0, MINUTES_PER_DAY)]);
+    }
+
+    #[test]
+    fn event_splits_free_time() {
+        let ev = timed_event("a", "2024-05-02T09:00:00+02:00", "2024-05-02T11:00:00+02:00");
+        let batches = batches_on("2024-05-02", vec![ev]);
+        assert_eq!(
+            spans(&batches),
+            vec![(false, 0, 540), (true, 540, 660), (false, 660, MINUTES_PER_DAY)]
+        );
+        assert_eq!(batches[1].events.len(), 1);
+    }
+
+    #[test]
+    fn back_to_back_events_get_their_own_batches() {
+        let a = timed_event("a", "2024-05-02T09:00:00+02:00", "2024-05-02T10:00:00+02:00");
+        let b = timed_event("b", "2024-05-02T10:00:00+02:00", "2024-05-02T11:00:00+02:00");
+        let batches = batches_on("2024-05-02", vec![a, b]);
+        assert_eq!(
+            spans(&batches),
+            vec![
+                (false, 0, 540),
+                (true, 540, 600),
+                (true, 600, 660),
+                (false, 660, MINUTES_PER_DAY)
+            ]
+        );
+    }
+
+    #[test]
+    fn all_day_events_are_listed_apart_from_the_batches() {
+        let tz = berlin();
+        let day = day_range(&tz, parse_date("2024-05-02").unwrap());
+        let events = vec![
+            all_day_event("bday", "2024-05-02", "2024-05-03"),
+            all_day_event("vacation", "2024-04-29", "2024-05-06"),
+            all_day_event("yesterday", "2024-05-01", "2024-05-02"),
+            timed_event("a", "2024-05-02T09:00:00+02:00", "2024-05-02T10:00:00+02:00"),
+        ];
+
+        let listed: Vec<_> = all_day_events(&events, day, &tz)
+            .into_iter()
+            .map(|ev| ev.id)
+            .collect();
+        assert_eq!(listed, ["bday", "vacation"]);
+
+        let batches = make_batches(day_blocks(60), events, day, &tz);
+        assert_eq!(
+            spans(&batches),
+            vec![(false, 0, 540), (true, 540, 600), (false, 600, MINUTES_PER_DAY)]
+        );
+    }
+
+    #[test]
+    fn events_outside_the_day_are_ignored() {
+        let ev = timed_event("a", "2024-05-01T09:00:00+02:00", "2024-05-01T10:00:00+02:00");
+        let batches = batches_on("2024-05-02", vec![ev]);
+        assert_eq!(spans(&batches), vec![(false, 0, MINUTES_PER_DAY)]);
+    }
+
+    #[test]
+    fn events_after_spring_forward_keep_their_wall_clock_time() {
+        // 02:00 doesn't exist in Berlin on 2024-03-31; 04:00 local is 02:00 UTC
+        let ev = timed_event("a", "2024-03-31T04:00:00+02:00", "2024-03-31T05:00:00+02:00");
+        let batches = batches_on("2024-03-31", vec![ev]);
+        assert_eq!(
+            spans(&batches),
+            vec![(false, 0, 240), (true, 240, 300), (false, 300, MINUTES_PER_DAY)]
+        );
+    }
+
+    #[test]
+    fn overnight_events_cover_the_part_of_the_day_they_overlap() {
+        let ev = timed_event("a", "2024-05-01T22:00:00+02:00", "2024-05-02T02:00:00+02:00");
+        let batches = batches_on("2024-05-02", vec![ev]);
+        assert_eq!(
+            spans(&batches),
+            vec![(true, 0, 120), (false, 120, MINUTES_PER_DAY)]
+        );
+    }
+}
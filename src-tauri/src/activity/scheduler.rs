@@ -0,0 +1,232 @@
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use reqwest::Client;
+use serde::Serialize;
+
+use crate::activity::activitywatch::SETTLE_DELAY_SECS;
+use crate::activity::commands::process_block;
+use crate::activity::granularity::{block_minutes, block_start, block_start_in, blocks_in};
+use crate::database::{self, settings};
+use crate::timezone::user_timezone;
+use crate::users::{active_user_id, LEGACY_USER_ID};
+
+/// End of the last block processed without gaps before it (RFC3339), kept per
+/// account as `<this>:<user id>`. Without a user id it is the legacy account's.
+const LAST_PROCESSED_SETTING: &str = "scheduler_last_processed";
+/// How many hours back a catch-up run may go
+const BACKFILL_LIMIT_SETTING: &str = "scheduler_backfill_limit_hours";
+const DEFAULT_BACKFILL_LIMIT_HOURS: i64 = 24;
+
+/// How often the loop looks at the wall clock. Short enough to notice a
+/// wake-up from sleep quickly, since tokio timers don't advance while suspended.
+const CHECK_INTERVAL_SECS: u64 = 60;
+
+#[derive(Debug, Serialize)]
+pub struct SchedulerStatus {
+    pub last_processed: Option<DateTime<Utc>>,
+    pub backfill_limit_hours: i64,
+}
+
+fn last_processed_key(user_id: i64) -> String {
+    format!("{}:{}", LAST_PROCESSED_SETTING, user_id)
+}
+
+fn load_status() -> Result<SchedulerStatus, String> {
+    let conn = database::connection();
+    let user_id = active_user_id(&conn);
+    let mut last_processed =
+        settings::get(&conn, &last_processed_key(user_id)).map_err(|e| e.to_string())?;
+    if last_processed.is_none() && user_id == LEGACY_USER_ID {
+        last_processed = settings::get(&conn, LAST_PROCESSED_SETTING).map_err(|e| e.to_string())?;
+    }
+    let last_processed = last_processed
+        .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+        .map(|dt| dt.with_timezone(&Utc));
+    let backfill_limit_hours = settings::get(&conn, BACKFILL_LIMIT_SETTING)
+        .map_err(|e| e.to_string())?
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_BACKFILL_LIMIT_HOURS);
+
+    Ok(SchedulerStatus {
+        last_processed,
+        backfill_limit_hours,
+    })
+}
+
+fn save_last_processed(end: DateTime<Utc>) -> Result<(), String> {
+    let conn = database::connection();
+    let key = last_processed_key(active_user_id(&conn));
+    settings::set(&conn, &key, &end.to_rfc3339()).map_err(|e| e.to_string())
+}
+
+/// Every complete block before `now` since the last processed one, oldest
+/// first, going back at most the backfill limit
+fn pending_blocks(
+    tz: &Tz,
+    status: &SchedulerStatus,
+    now: DateTime<Utc>,
+    minutes: i64,
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let until = block_start_in(tz, now, minutes);
+    let earliest = block_start_in(
+        tz,
+        until - Duration::hours(status.backfill_limit_hours.max(1)),
+        minutes,
+    );
+
+    let start = match status.last_processed {
+        // Snap to the block grid in case the block size changed since
+        Some(last) => block_start_in(tz, last, minutes).max(earliest),
+        // Fresh install: only the block that just finished
+        None => block_start_in(tz, until - Duration::minutes(1), minutes),
+    };
+    blocks_in(tz, start, until, minutes)
+}
+
+/// Process every complete block since the last processed one, oldest first,
+/// going back at most the configured limit.
+///
+/// The marker only advances while blocks succeed, so a block that failed is
+/// retried on the next run; blocks after it are already stored and cost nothing.
+pub async fn catch_up(client: &Client) -> Result<usize, String> {
+    let status = load_status()?;
+    let tz = user_timezone();
+    let minutes = block_minutes();
+
+    let mut processed = 0;
+    let mut contiguous = true;
+    for (current, end) in pending_blocks(&tz, &status, Utc::now(), minutes) {
+        match process_block(client, current, end).await {
+            Ok(_) => {
+                processed += 1;
+                if contiguous {
+                    save_last_processed(end)?;
+                }
+            }
+            Err(e) => {
+                eprintln!("Scheduled update for {} → {} failed: {}", current, end, e);
+                contiguous = false;
+            }
+        }
+    }
+
+    Ok(processed)
+}
+
+/// Background loop: catch up on launch, then on every block boundary
+/// (including the ones crossed while the machine was asleep)
+pub async fn run() {
+    let client = Client::new();
+    let mut next_run = Utc::now();
+
+    loop {
+        if Utc::now() >= next_run {
+            println!("🔄 Running scheduled update...");
+            match catch_up(&client).await {
+                Ok(0) => println!("✅ Scheduled update: nothing to do"),
+                Ok(n) => println!("✅ Scheduled update processed {} block(s)", n),
+                Err(e) => eprintln!("Background update error: {:?}", e),
+            }
+            let minutes = block_minutes();
+            next_run = block_start(Utc::now(), minutes)
+                + Duration::minutes(minutes)
+                + Duration::seconds(SETTLE_DELAY_SECS);
+        }
+
+        tokio::time::sleep(std::time::Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+    }
+}
+
+#[tauri::command]
+pub fn get_scheduler_status() -> Result<SchedulerStatus, String> {
+    load_status()
+}
+
+/// Set how many hours back a catch-up run may backfill
+#[tauri::command]
+pub fn set_backfill_limit(hours: i64) -> Result<(), String> {
+    if hours < 1 {
+        return Err("Backfill limit must be at least 1 hour".into());
+    }
+    let conn = database::connection();
+    settings::set(&conn, BACKFILL_LIMIT_SETTING, &hours.to_string()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn berlin() -> Tz {
+        "Europe/Berlin".parse().unwrap()
+    }
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    fn status(last_processed: Option<&str>) -> SchedulerStatus {
+        SchedulerStatus {
+            last_processed: last_processed.map(utc),
+            backfill_limit_hours: 24,
+        }
+    }
+
+    #[test]
+    fn blocks_restart_at_midnight_after_a_23_hour_day() {
+        // 2024-03-31 is 23 hours long in Berlin: its last 90-minute block is
+        // cut at midnight (22:00Z) and 1 April starts a fresh grid there
+        let blocks = pending_blocks(
+            &berlin(),
+            &status(Some("2024-03-31T20:00:00Z")),
+            utc("2024-04-01T03:00:00Z"),
+            90,
+        );
+        let expected = [
+            ("2024-03-31T20:00:00Z", "2024-03-31T21:30:00Z"),
+            ("2024-03-31T21:30:00Z", "2024-03-31T22:00:00Z"),
+            ("2024-03-31T22:00:00Z", "2024-03-31T23:30:00Z"),
+            ("2024-03-31T23:30:00Z", "2024-04-01T01:00:00Z"),
+            ("2024-04-01T01:00:00Z", "2024-04-01T02:30:00Z"),
+        ];
+        assert_eq!(
+            blocks,
+            expected.map(|(start, end)| (utc(start), utc(end))).to_vec()
+        );
+    }
+
+    #[test]
+    fn blocks_stay_on_the_grid_across_a_25_hour_day() {
+        let tz = berlin();
+        let blocks = pending_blocks(
+            &tz,
+            &status(Some("2024-10-26T20:00:00Z")),
+            utc("2024-10-28T12:00:00Z"),
+            90,
+        );
+        for (start, end) in &blocks {
+            assert_eq!(block_start_in(&tz, *start, 90), *start);
+            assert_eq!(block_start_in(&tz, *end, 90), *end);
+        }
+        assert!(blocks.windows(2).all(|pair| pair[0].1 == pair[1].0));
+        // Midnight of 28 October (CET) is a block boundary
+        assert!(blocks.iter().any(|(start, _)| *start == utc("2024-10-27T23:00:00Z")));
+    }
+
+    #[test]
+    fn fresh_install_processes_only_the_last_block() {
+        let blocks = pending_blocks(&berlin(), &status(None), utc("2024-05-02T10:20:00Z"), 60);
+        assert_eq!(blocks, [(utc("2024-05-02T09:00:00Z"), utc("2024-05-02T10:00:00Z"))]);
+    }
+
+    #[test]
+    fn backfill_stops_at_the_limit() {
+        let blocks = pending_blocks(
+            &berlin(),
+            &status(Some("2024-04-20T00:00:00Z")),
+            utc("2024-05-02T10:00:00Z"),
+            60,
+        );
+        assert_eq!(blocks.len(), 24);
+        assert_eq!(blocks[0].0, utc("2024-05-01T10:00:00Z"));
+    }
+}
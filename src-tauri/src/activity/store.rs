@@ -0,0 +1,293 @@
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::activity::activitywatch::SETTLE_DELAY_SECS;
+use crate::activity::categorize::{CategoryTotal, Productivity};
+use crate::database;
+use crate::users::active_user_id;
+
+pub const SYNC_PENDING: &str = "pending";
+pub const SYNC_SYNCED: &str = "synced";
+pub const SYNC_FAILED: &str = "failed";
+
+/// One processed block of ActivityWatch data, as stored in `activity_blocks`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ActivityBlock {
+    pub id: i64,
+    pub block_start: DateTime<Utc>,
+    pub block_end: DateTime<Utc>,
+    pub title: String,
+    pub raw_text: String,
+    pub llm_summary: Option<String>,
+    pub app_durations: BTreeMap<String, f64>,
+    pub active_seconds: f64,
+    pub afk_seconds: f64,
+    pub sync_status: String,
+    pub sync_error: Option<String>,
+    pub calendar_event_id: Option<String>,
+    /// When ActivityWatch data was last read for the block
+    pub summarized_at: Option<DateTime<Utc>>,
+    pub categories: Vec<CategoryTotal>,
+}
+
+/// Data needed to store a freshly summarized block
+pub struct NewActivityBlock {
+    pub block_start: DateTime<Utc>,
+    pub block_end: DateTime<Utc>,
+    pub title: String,
+    pub raw_text: String,
+    pub llm_summary: Option<String>,
+    pub app_durations: BTreeMap<String, f64>,
+    pub afk_seconds: f64,
+    pub categories: Vec<CategoryTotal>,
+}
+
+const BLOCK_COLUMNS: &str =
+    "id, block_start, block_end, title, raw_text, llm_summary, app_durations,
+     active_seconds, afk_seconds, sync_status, sync_error, calendar_event_id, summarized_at";
+
+fn parse_time(value: String) -> rusqlite::Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(&value)
+        .map(|dt| dt.with_timezone(&Utc))
+        .map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+        })
+}
+
+fn block_from_row(row: &Row) -> rusqlite::Result<ActivityBlock> {
+    let app_durations: String = row.get(6)?;
+    Ok(ActivityBlock {
+        id: row.get(0)?,
+        block_start: parse_time(row.get(1)?)?,
+        block_end: parse_time(row.get(2)?)?,
+        title: row.get(3)?,
+        raw_text: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+        llm_summary: row.get(5)?,
+        app_durations: serde_json::from_str(&app_durations).unwrap_or_default(),
+        active_seconds: row.get(7)?,
+        afk_seconds: row.get(8)?,
+        sync_status: row.get(9)?,
+        sync_error: row.get(10)?,
+        calendar_event_id: row.get(11)?,
+        summarized_at: row
+            .get::<_, Option<String>>(12)?
+            .map(parse_time)
+            .transpose()?,
+        categories: Vec::new(),
+    })
+}
+
+fn load_categories(conn: &Connection, block: &mut ActivityBlock) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare(
+        "SELECT category, productivity, seconds FROM activity_block_categories
+         WHERE activity_block_id = ?1 ORDER BY seconds DESC",
+    )?;
+    let rows = stmt.query_map(params![block.id], |row| {
+        Ok(CategoryTotal {
+            category: row.get(0)?,
+            productivity: Productivity::parse(&row.get::<_, String>(1)?),
+            seconds: row.get(2)?,
+        })
+    })?;
+    block.categories = rows.collect::<rusqlite::Result<_>>()?;
+    Ok(())
+}
+
+fn save_categories(
+    conn: &Connection,
+    block_id: i64,
+    categories: &[CategoryTotal],
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM activity_block_categories WHERE activity_block_id = ?1",
+        params![block_id],
+    )?;
+    for c in categories {
+        conn.execute(
+            "INSERT INTO activity_block_categories (activity_block_id, category, productivity, seconds)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![block_id, c.category, c.productivity.as_str(), c.seconds],
+        )?;
+    }
+    Ok(())
+}
+
+impl ActivityBlock {
+    /// Whether the block was summarized after ActivityWatch had all its data,
+    /// so summarizing it again would give the same result
+    pub fn is_final(&self) -> bool {
+        self.summarized_at
+            .is_some_and(|at| at >= self.block_end + Duration::seconds(SETTLE_DELAY_SECS))
+    }
+
+    /// Whether there was activity to summarize but the summary failed
+    pub fn needs_summary(&self) -> bool {
+        self.llm_summary.is_none() && !self.raw_text.is_empty()
+    }
+
+    /// Text pushed as the calendar event description; the raw activity until
+    /// a summary exists
+    pub fn description(&self) -> String {
+        let mut description = match (&self.llm_summary, self.raw_text.is_empty()) {
+            (Some(summary), _) => summary.clone(),
+            (None, false) => self.raw_text.clone(),
+            (None, true) => "No activity recorded for this period.".to_string(),
+        };
+        if let Some(line) = super::summarize::afk_line(self.afk_seconds) {
+            description.push_str(&format!("\n\n{}", line));
+        }
+        description
+    }
+}
+
+/// Insert or replace the block for `[block_start, block_end)`, resetting its sync status
+pub fn save_block(conn: &Connection, block: &NewActivityBlock) -> rusqlite::Result<ActivityBlock> {
+    let now = Utc::now().to_rfc3339();
+    let app_durations = serde_json::to_string(&block.app_durations)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    let active_seconds: f64 = block.app_durations.values().sum();
+
+    conn.execute(
+        "INSERT INTO activity_blocks (
+            user_id, block_start, block_end, title, raw_text, llm_summary, app_durations,
+            active_seconds, afk_seconds, sync_status, summarized_at, created_at, updated_at
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?11, ?11)
+        ON CONFLICT(user_id, block_start, block_end) DO UPDATE SET
+            title = excluded.title,
+            raw_text = excluded.raw_text,
+            llm_summary = excluded.llm_summary,
+            app_durations = excluded.app_durations,
+            active_seconds = excluded.active_seconds,
+            afk_seconds = excluded.afk_seconds,
+            sync_status = excluded.sync_status,
+            sync_error = NULL,
+            summarized_at = excluded.summarized_at,
+            updated_at = excluded.updated_at",
+        params![
+            active_user_id(conn),
+            block.block_start.to_rfc3339(),
+            block.block_end.to_rfc3339(),
+            block.title,
+            block.raw_text,
+            block.llm_summary,
+            app_durations,
+            active_seconds,
+            block.afk_seconds,
+            SYNC_PENDING,
+            now
+        ],
+    )?;
+
+    let mut saved = find_block(conn, block.block_start, block.block_end)?
+        .ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+    save_categories(conn, saved.id, &block.categories)?;
+    saved.categories = block.categories.clone();
+    Ok(saved)
+}
+
+/// Stored block covering exactly `[start, end)`, if any
+pub fn find_block(
+    conn: &Connection,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> rusqlite::Result<Option<ActivityBlock>> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM activity_blocks
+             WHERE user_id = ?1 AND block_start = ?2 AND block_end = ?3",
+            BLOCK_COLUMNS
+        ),
+        params![active_user_id(conn), start.to_rfc3339(), end.to_rfc3339()],
+        block_from_row,
+    )
+    .optional()?
+    .map(|mut block| load_categories(conn, &mut block).map(|_| block))
+    .transpose()
+}
+
+pub fn get_block(conn: &Connection, id: i64) -> rusqlite::Result<Option<ActivityBlock>> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM activity_blocks WHERE id = ?1",
+            BLOCK_COLUMNS
+        ),
+        params![id],
+        block_from_row,
+    )
+    .optional()?
+    .map(|mut block| load_categories(conn, &mut block).map(|_| block))
+    .transpose()
+}
+
+/// All stored blocks starting inside `[start, end)`, oldest first
+pub fn blocks_between(
+    conn: &Connection,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> rusqlite::Result<Vec<ActivityBlock>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM activity_blocks
+         WHERE user_id = ?1 AND block_start >= ?2 AND block_start < ?3
+         ORDER BY block_start",
+        BLOCK_COLUMNS
+    ))?;
+    let mut blocks = stmt
+        .query_map(
+            params![active_user_id(conn), start.to_rfc3339(), end.to_rfc3339()],
+            block_from_row,
+        )?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for block in &mut blocks {
+        load_categories(conn, block)?;
+    }
+    Ok(blocks)
+}
+
+pub fn mark_synced(conn: &Connection, id: i64, event_id: Option<&str>) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE activity_blocks
+         SET sync_status = ?1, sync_error = NULL,
+             calendar_event_id = COALESCE(?2, calendar_event_id), updated_at = ?3
+         WHERE id = ?4",
+        params![SYNC_SYNCED, event_id, Utc::now().to_rfc3339(), id],
+    )?;
+    Ok(())
+}
+
+/// Add the summary of a block stored without one; its calendar event is pushed again
+pub fn set_summary(conn: &Connection, id: i64, summary: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE activity_blocks
+         SET llm_summary = ?1, sync_status = ?2, sync_error = NULL, updated_at = ?3
+         WHERE id = ?4",
+        params![summary, SYNC_PENDING, Utc::now().to_rfc3339(), id],
+    )?;
+    Ok(())
+}
+
+pub fn mark_failed(conn: &Connection, id: i64, error: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE activity_blocks SET sync_status = ?1, sync_error = ?2, updated_at = ?3 WHERE id = ?4",
+        params![SYNC_FAILED, error, Utc::now().to_rfc3339(), id],
+    )?;
+    Ok(())
+}
+
+/// Stored activity blocks between two RFC3339 timestamps
+#[tauri::command]
+pub fn get_activity_blocks(
+    start_iso: String,
+    end_iso: String,
+) -> Result<Vec<ActivityBlock>, String> {
+    let start = DateTime::parse_from_rfc3339(&start_iso)
+        .map_err(|e| format!("Invalid start time: {}", e))?
+        .with_timezone(&Utc);
+    let end = DateTime::parse_from_rfc3339(&end_iso)
+        .map_err(|e| format!("Invalid end time: {}", e))?
+        .with_timezone(&Utc);
+
+    let conn = database::connection();
+    blocks_between(&conn, start, end).map_err(|e| e.to_string())
+}
@@ -1,10 +1,12 @@
 use crate::activity::activitywatch::AwEvent;
+use crate::activity::categorize::Categorizer;
+use crate::llm::{active_provider, LlmRequest};
 use reqwest::Client;
-use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 
 // === 3. Summarize events (like Python) ===
-pub fn summarize_events(events: &[AwEvent]) -> (String, String) {
+pub fn summarize_events(events: &[AwEvent], categorizer: &Categorizer) -> (String, String) {
     use std::collections::{HashMap, HashSet};
     let mut total_time = 0.0;
     let mut app_usage: HashMap<String, f64> = HashMap::new();
@@ -93,50 +95,62 @@ pub fn summarize_events(events: &[AwEvent]) -> (String, String) {
         }
     }
 
+    // --- Categories from the rules engine ---
+    let categories = categorizer.totals(events);
+    if !categories.is_empty() {
+        raw_lines.push("\nCategories:".into());
+        for c in &categories {
+            raw_lines.push(format!(
+                "   • {} [{}] (~{:.1}m, {:.1}%)",
+                c.category,
+                c.productivity.as_str(),
+                c.seconds / 60.0,
+                (c.seconds / total_time) * 100.0
+            ));
+        }
+    }
+
     let raw_text = raw_lines.join("\n");
 
     (event_title, raw_text) // raw_text will go to Ollama
 }
 
-
-pub async fn summarize_with_ollama(client: &Client, raw_text: &str) -> Result<String, String> {
-    #[derive(Serialize)]
-    struct Payload<'a> {
-        model: &'a str,
-        prompt: String,
-        stream: bool,
+/// Seconds spent per app (lowercased, like the summary title)
+pub fn app_durations(events: &[AwEvent]) -> BTreeMap<String, f64> {
+    let mut durations = BTreeMap::new();
+    for ev in events {
+        let app = ev
+            .data
+            .app
+            .clone()
+            .unwrap_or_else(|| "Unknown".to_string())
+            .to_lowercase();
+        *durations.entry(app).or_default() += ev.duration;
     }
+    durations
+}
 
-    #[derive(Deserialize)]
-    struct OllamaResponse {
-        response: String,
+/// Separate line appended to calendar descriptions for time spent AFK
+pub fn afk_line(afk_seconds: f64) -> Option<String> {
+    if afk_seconds < 60.0 {
+        return None;
     }
+    Some(format!("AFK: ~{:.0}m", afk_seconds / 60.0))
+}
 
-    let payload = Payload {
-        model: "mistral",
-        prompt: format!(
+pub async fn summarize_with_llm(client: &Client, raw_text: &str) -> Result<String, String> {
+    let provider = active_provider()?;
+    let request = LlmRequest::new(
+        format!(
             "Summarize the activity into 3-5 concise bullet points.\n- Keep each bullet under 80 chars.\n- Focus on main apps and tasks.\n- No preamble or closing text.\n\nRaw log:\n{}",
             raw_text
         ),
-        stream: false,
-    };
-
-    let resp = client
-        .post("http://localhost:11434/api/generate")
-        .json(&payload)
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
-
-    if !resp.status().is_success() {
-        return Err(format!("Ollama failed: {}", resp.status()));
-    }
-
-    let data: OllamaResponse = resp.json().await.map_err(|e| e.to_string())?;
+        raw_text,
+    );
 
-    let mut summary = data.response.replace("\\n", "\n").trim().to_string();
-    if summary.len() > 600 {
-        summary.truncate(600);
+    let mut summary = provider.generate(client, &request).await?;
+    if summary.chars().count() > 600 {
+        summary = summary.chars().take(600).collect();
         summary.push_str("\n…");
     }
 
@@ -1,30 +1,150 @@
+use crate::activity::calendar::ApiError;
 use crate::database;
-use chrono::{DateTime, Utc};
+use crate::secrets;
+use crate::users::active_user_id;
+use chrono::{DateTime, Duration, Utc};
+use reqwest::{Client, StatusCode};
+use rusqlite::params;
+use serde::Deserialize;
+use std::future::Future;
 
-#[derive(Debug)]
+/// Refresh this long before the stored expiry so in-flight requests don't race it
+const REFRESH_MARGIN_SECS: i64 = 120;
+
+#[derive(Debug, Clone)]
 pub struct CalendarToken {
+    pub id: i64,
+    pub credential_id: i64,
     pub access_token: String,
     pub refresh_token: String,
     pub expiry_date: DateTime<Utc>,
 }
 
+#[derive(Deserialize)]
+struct RefreshResponse {
+    access_token: String,
+    expires_in: i64,
+    refresh_token: Option<String>,
+}
+
+/// Most recent token of the active user
 pub fn get_latest_token() -> Result<CalendarToken, String> {
     let conn = database::connection();
     let mut stmt = conn
-        .prepare("SELECT access_token, refresh_token, expiry_date FROM calendar_tokens ORDER BY created_at DESC LIMIT 1")
+        .prepare("SELECT id, credential_id, access_token, refresh_token, expiry_date FROM calendar_tokens WHERE user_id = ?1 ORDER BY created_at DESC LIMIT 1")
         .map_err(|e| e.to_string())?;
 
-    let row: (String, String, String) = stmt
-        .query_row([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+    let row: (i64, i64, String, Option<String>, String) = stmt
+        .query_row(params![active_user_id(&conn)], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+        })
         .map_err(|_| "No token found".to_string())?;
 
-    let expiry = DateTime::parse_from_rfc3339(&row.2)
+    let expiry = DateTime::parse_from_rfc3339(&row.4)
         .map_err(|e| e.to_string())?
         .with_timezone(&Utc);
 
     Ok(CalendarToken {
-        access_token: row.0,
-        refresh_token: row.1,
+        id: row.0,
+        credential_id: row.1,
+        access_token: secrets::decrypt(&row.2)?,
+        refresh_token: secrets::decrypt(&row.3.unwrap_or_default())?,
         expiry_date: expiry,
     })
 }
+
+/// Exchange the stored refresh token for a new access token and save it
+pub async fn refresh_token(client: &Client, token: &CalendarToken) -> Result<CalendarToken, String> {
+    if token.refresh_token.is_empty() {
+        return Err("No refresh token stored, please log in with Google again".into());
+    }
+
+    let (client_id, client_secret, token_uri) = {
+        let conn = database::connection();
+        conn.query_row(
+            "SELECT client_id, client_secret, token_uri FROM credentials WHERE id = ?1",
+            params![token.credential_id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            },
+        )
+        .map_err(|e| format!("Failed to query credentials: {}", e))?
+    };
+    let client_secret = secrets::decrypt(&client_secret)?;
+
+    let resp = client
+        .post(&token_uri)
+        .form(&[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", token.refresh_token.as_str()),
+            ("client_id", client_id.as_str()),
+            ("client_secret", client_secret.as_str()),
+        ])
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !resp.status().is_success() {
+        return Err(format!("Token refresh failed: {}", resp.status()));
+    }
+
+    let data: RefreshResponse = resp.json().await.map_err(|e| e.to_string())?;
+    let expiry = Utc::now() + Duration::seconds(data.expires_in);
+    // Google normally keeps the old refresh token valid and omits it here
+    let refresh = data.refresh_token.unwrap_or_else(|| token.refresh_token.clone());
+
+    {
+        let conn = database::connection();
+        conn.execute(
+            "UPDATE calendar_tokens
+             SET access_token = ?1, refresh_token = ?2, expiry_date = ?3, updated_at = ?4
+             WHERE id = ?5",
+            params![
+                secrets::encrypt(&data.access_token)?,
+                secrets::encrypt(&refresh)?,
+                expiry.to_rfc3339(),
+                Utc::now().to_rfc3339(),
+                token.id
+            ],
+        )
+        .map_err(|e| format!("Failed to save refreshed token: {}", e))?;
+    }
+    println!("🔑 Access token refreshed, valid until {}", expiry);
+
+    Ok(CalendarToken {
+        access_token: data.access_token,
+        refresh_token: refresh,
+        expiry_date: expiry,
+        ..token.clone()
+    })
+}
+
+/// Latest token, refreshed first if it expires within the safety margin
+pub async fn fresh_token(client: &Client) -> Result<CalendarToken, String> {
+    let token = get_latest_token()?;
+    if Utc::now() + Duration::seconds(REFRESH_MARGIN_SECS) < token.expiry_date {
+        return Ok(token);
+    }
+    refresh_token(client, &token).await
+}
+
+/// Run `op` with a valid access token, refreshing and retrying once on a 401
+pub async fn with_access_token<T, F, Fut>(client: &Client, mut op: F) -> Result<T, ApiError>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<T, ApiError>>,
+{
+    let token = fresh_token(client).await?;
+    match op(token.access_token.clone()).await {
+        Err(e) if e.is(StatusCode::UNAUTHORIZED) => {
+            println!("🔑 Got 401, refreshing token and retrying once");
+            let token = refresh_token(client, &token).await?;
+            op(token.access_token).await
+        }
+        result => result,
+    }
+}
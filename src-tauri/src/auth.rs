@@ -1,9 +1,11 @@
 use crate::database;
+use crate::secrets;
+use crate::users::{self, active_user_id};
 use rusqlite::params;
 use serde::{Deserialize, Serialize};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpListener;
-use chrono::{DateTime, Utc};
+use chrono::Utc;
 
 #[derive(Deserialize)]
 struct GoogleTokenResponse {
@@ -20,13 +22,6 @@ pub struct AuthResult {
     message: String,
 }
 
-#[derive(Debug, Deserialize, Clone)]
-pub struct CalendarToken {
-    pub access_token: String,
-    pub refresh_token: String,
-    pub expiry_date: DateTime<Utc>,
-}
-
 #[tauri::command]
 pub async fn login_with_google() -> Result<AuthResult, String> {
     let (credential_id, client_id, client_secret, _redirect_uris, scopes) = {
@@ -47,6 +42,7 @@ pub async fn login_with_google() -> Result<AuthResult, String> {
         println!("Credential ID: {}", row.0); // Log credential_id
         row
     };
+    let client_secret = secrets::decrypt(&client_secret)?;
 
     let listener = TcpListener::bind("localhost:0")
         .await
@@ -76,7 +72,13 @@ pub async fn login_with_google() -> Result<AuthResult, String> {
     let mut buffer = [0u8; 2048];
     let n = socket.read(&mut buffer).await.map_err(|e| e.to_string())?;
     let req_str = String::from_utf8_lossy(&buffer[..n]);
-    println!("Raw request: {}", req_str);
+    // The request line carries the auth code, so only the path is logged
+    let path = req_str
+        .split_whitespace()
+        .nth(1)
+        .and_then(|target| target.split('?').next())
+        .unwrap_or_default();
+    println!("Callback request: {}", path);
 
     let mut code = None;
     if req_str.contains("/oauth2callback") {
@@ -88,7 +90,7 @@ pub async fn login_with_google() -> Result<AuthResult, String> {
                     for param in query_params.split('&') {
                         if param.starts_with("code=") {
                             code = Some(urlencoding::decode(&param[5..]).unwrap_or_default().to_string());
-                            println!("Auth code: {}", code.as_ref().unwrap());
+                            println!("Auth code received");
                             break;
                         }
                     }
@@ -121,7 +123,11 @@ pub async fn login_with_google() -> Result<AuthResult, String> {
                 e.to_string()
             })?;
             println!("3 true");
+            let profile = users::fetch_profile(&client, &token_json.access_token).await?;
+            let access_token = secrets::encrypt(&token_json.access_token)?;
+            let refresh_token = secrets::encrypt(&token_json.refresh_token.unwrap_or_default())?;
             let conn = database::connection();
+            let user_id = users::upsert_user(&conn, &profile).map_err(|e| e.to_string())?;
             let now = Utc::now().to_rfc3339();
             println!("4 true");
             conn.execute(
@@ -129,10 +135,10 @@ pub async fn login_with_google() -> Result<AuthResult, String> {
                     user_id, credential_id, access_token, refresh_token, scope, token_type, expiry_date, created_at, updated_at
                 ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8)",
                 params![
-                    1, // TODO: Replace with dynamic user_id
+                    user_id,
                     credential_id,
-                    token_json.access_token,
-                    token_json.refresh_token.unwrap_or_default(),
+                    access_token,
+                    refresh_token,
                     token_json.scope,
                     token_json.token_type,
                     Utc::now()
@@ -146,6 +152,8 @@ pub async fn login_with_google() -> Result<AuthResult, String> {
                 println!("Database insert error: {}", e);
                 format!("Database insert failed: {}", e)
             })?;
+            // Signing in makes the account the active one
+            users::set_active_user(&conn, user_id).map_err(|e| e.to_string())?;
 
             "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n<h1>Auth successful!</h1><p>You can close this window now.</p>"
         } else {
@@ -171,31 +179,39 @@ pub async fn check_calendar_token() -> Result<bool, String> {
     use reqwest::Client;
 
     // Query the DB in a short scope so the MutexGuard is dropped before awaits
-    let (access_token, expiry_date, _refresh_token) = {
+    let (access_token, expiry_date) = {
         let conn = database::connection();
         let mut stmt = conn
-            .prepare("SELECT access_token, expiry_date, refresh_token FROM calendar_tokens ORDER BY created_at DESC LIMIT 1")
+            .prepare("SELECT access_token, expiry_date FROM calendar_tokens WHERE user_id = ?1 ORDER BY created_at DESC LIMIT 1")
             .map_err(|e| e.to_string())?;
-        let token_row: Result<(String, String, String), _> =
-            stmt.query_row([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)));
+        let token_row: Result<(String, String), _> = stmt.query_row(
+            params![active_user_id(&conn)],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        );
         match token_row {
             Ok(data) => data,
             Err(_) => return Ok(false), // no token saved
         }
     };
-    println!("Access token: {}", access_token);
-    println!("Expiry date: {}", expiry_date);
-    println!("Refresh token: {}", _refresh_token);
+    println!("Token expiry date: {}", expiry_date);
     // Check expiry
     let expiry = chrono::DateTime::parse_from_rfc3339(&expiry_date)
         .map_err(|e| e.to_string())?;
-    if chrono::Utc::now() > expiry {
-        println!("expired");
-        return Ok(false); // expired
-    }
+    let client = Client::new();
+    let access_token = if chrono::Utc::now() > expiry {
+        println!("expired, refreshing");
+        match crate::activity::token::fresh_token(&client).await {
+            Ok(token) => token.access_token,
+            Err(e) => {
+                eprintln!("Token refresh failed: {}", e);
+                return Ok(false);
+            }
+        }
+    } else {
+        secrets::decrypt(&access_token)?
+    };
 
     // Verify with Google API
-    let client = Client::new();
     let resp = client
         .get("https://www.googleapis.com/calendar/v3/users/me/calendarList")
         .bearer_auth(&access_token)
@@ -205,30 +221,3 @@ pub async fn check_calendar_token() -> Result<bool, String> {
     println!("Response: {}", resp.status());
     Ok(resp.status().is_success())
 }
-
-/// Get the latest saved calendar token from the database
-pub fn get_latest_token() -> Result<CalendarToken, String> {
-    let conn = database::connection();
-    let mut stmt = conn
-        .prepare(
-            "SELECT access_token, refresh_token, expiry_date 
-             FROM calendar_tokens 
-             ORDER BY created_at DESC 
-             LIMIT 1",
-        )
-        .map_err(|e| e.to_string())?;
-
-    let row: (String, String, String) = stmt
-        .query_row([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
-        .map_err(|_| "No token found".to_string())?;
-
-    let expiry = DateTime::parse_from_rfc3339(&row.2)
-        .map_err(|e| e.to_string())?
-        .with_timezone(&Utc);
-
-    Ok(CalendarToken {
-        access_token: row.0,
-        refresh_token: row.1,
-        expiry_date: expiry,
-    })
-}
\ No newline at end of file
@@ -1,56 +1,37 @@
-use chrono::{DateTime, Local, TimeZone, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
 use reqwest::Client;
-use serde_json::Value;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
 
-/// Get today’s start + end in UTC (from local time)
-fn today_range_utc() -> (DateTime<Utc>, DateTime<Utc>) {
-    let local_today = Local::now().date_naive();
-    let start_local = local_today.and_hms_opt(0, 0, 0).unwrap();
-    let end_local = local_today.and_hms_opt(23, 59, 59).unwrap();
-
-    let start_local_dt = Local.from_local_datetime(&start_local).single().unwrap();
-    let end_local_dt = Local.from_local_datetime(&end_local).single().unwrap();
-
-    (start_local_dt.with_timezone(&Utc), end_local_dt.with_timezone(&Utc))
-}
-
-
-/// Fetch events from Google Calendar for today
-pub async fn get_calendar_events(client: &Client, token: &str, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Value>, String> {
-    let url = "https://www.googleapis.com/calendar/v3/calendars/primary/events";
-
-    let resp = client
-        .get(url)
-        .bearer_auth(token)
-        .query(&[
-            ("timeMin", start.to_rfc3339()),
-            ("timeMax", end.to_rfc3339()),
-            ("singleEvents", "true".to_string()),
-            ("orderBy", "startTime".to_string()),
-        ])
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
-
-    if !resp.status().is_success() {
-        let status = resp.status();
-        let body_text = resp.text().await.unwrap_or_else(|_| "<no body>".to_string());
-        return Err(format!("Google Calendar API failed: {} - {}", status, body_text));
-    }
-
-    let data: Value = resp.json().await.map_err(|e| e.to_string())?;
-    Ok(data["items"].as_array().cloned().unwrap_or_default())
-}
+use crate::activity::{
+    calendar::CalendarEvent,
+    calendars::load_plan_events,
+    categorize::Productivity,
+    day_view::{overlap_minutes, COMPLETED_RATIO, OVERRUN_RATIO},
+    models::{RealityCheck, Suggestion},
+    store::{self, ActivityBlock},
+};
+use crate::database;
+use crate::llm::{
+    active_provider, provider::LlmProvider, structured::{generate_json, is_invalid_json}, template::TemplateProvider,
+    LlmRequest,
+};
+use crate::timezone::{date_span, day_range, parse_date, today, user_timezone};
+use crate::users::active_user_id;
 
 /// Collect event summaries + descriptions into one log string
-fn collect_descriptions(events: &[Value]) -> String {
+fn collect_descriptions(events: &[CalendarEvent], tz: &Tz) -> String {
     let mut logs = Vec::new();
 
     for ev in events {
-        let summary = ev.get("summary").and_then(|s| s.as_str()).unwrap_or("No title");
-        let desc = ev.get("description").and_then(|d| d.as_str()).unwrap_or("");
-        let start = ev["start"].get("dateTime").or_else(|| ev["start"].get("date")).and_then(|s| s.as_str()).unwrap_or("");
-        let end = ev["end"].get("dateTime").or_else(|| ev["end"].get("date")).and_then(|s| s.as_str()).unwrap_or("");
+        let summary = ev.title();
+        let desc = ev.description.as_deref().unwrap_or("");
+        let (start, end) = if ev.is_all_day() {
+            (ev.start.label(tz), "all day".to_string())
+        } else {
+            (ev.start.label(tz), ev.end.label(tz))
+        };
 
         if !desc.is_empty() {
             logs.push(format!("• {} ({} → {})\n  {}", summary, start, end, desc));
@@ -61,58 +42,578 @@ fn collect_descriptions(events: &[Value]) -> String {
 
     logs.join("\n")
 }
-/// Summarize events using Ollama
-async fn summarize_day(client: &Client, raw_text: &str) -> Result<String, String> {
-    #[derive(serde::Serialize)]
-    struct Payload<'a> {
-        model: &'a str,
-        prompt: String,
-        stream: bool,
-    }
 
-    #[derive(serde::Deserialize)]
-    struct OllamaResponse {
-        response: String,
+/// Stored activity blocks as log lines
+fn collect_activity(blocks: &[ActivityBlock], tz: &Tz) -> String {
+    blocks
+        .iter()
+        .map(|block| {
+            let start = block.block_start.with_timezone(tz).format("%Y-%m-%d %H:%M");
+            let end = block.block_end.with_timezone(tz).format("%H:%M");
+            match &block.llm_summary {
+                Some(summary) => format!("• {} ({} → {})\n  {}", block.title, start, end, summary),
+                None => format!("• {} ({} → {})", block.title, start, end),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// "today", "2024-05-02" or "2024-05-01 to 2024-05-07"
+fn span_label(tz: &Tz, first: NaiveDate, last: NaiveDate) -> String {
+    if first == last && first == today(tz) {
+        "today".to_string()
+    } else if first == last {
+        first.format("%Y-%m-%d").to_string()
+    } else {
+        format!("{} to {}", first.format("%Y-%m-%d"), last.format("%Y-%m-%d"))
     }
+}
 
-    let payload = Payload {
-        model: "mistral",
-        prompt: format!(
-            "You are a brutally honest productivity coach.\n\nSummarize today’s logs:\n{}\n\nGive:\n1. Reality Check\n2. Brutal Strategy\n3. Fixes (3 action points).",
-            raw_text
-        ),
-        stream: false,
+/// Most suggestions a review may hold
+const MAX_SUGGESTIONS: usize = 5;
+
+/// What the coach makes of a day (or span of days)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DayReview {
+    /// What actually happened, compared to the plan
+    pub summary: String,
+    /// The blunt strategy for doing better
+    pub strategy: String,
+    pub reality_check: RealityCheck,
+    pub suggestions: Vec<Suggestion>,
+}
+
+/// The part of a review the model writes; the numbers are measured, not asked for
+#[derive(Debug, Deserialize)]
+struct ReviewAnswer {
+    summary: String,
+    strategy: String,
+    suggestions: Vec<Suggestion>,
+}
+
+/// A summary stored in `daily_summary`
+#[derive(Debug, Serialize, Clone)]
+pub struct StoredSummary {
+    /// `YYYY-MM-DD`
+    pub date: String,
+    /// Timed plans of the day
+    pub total_planned: i64,
+    /// Plans with at least `COMPLETED_RATIO` of their time tracked as active
+    pub total_completed: i64,
+    pub total_pomodoros: i64,
+    pub total_distractions: i64,
+    /// Completed share of the plans, 0-100
+    pub reality_score: i64,
+    pub review: DayReview,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Numbers stored next to the summary text
+struct DayMetrics {
+    total_planned: i64,
+    total_completed: i64,
+    total_pomodoros: i64,
+    total_distractions: i64,
+    reality_score: i64,
+    /// Measured from the plans and tracked blocks, handed to the model as facts
+    reality: RealityCheck,
+}
+
+/// Active minutes tracked during `range`, assuming activity is spread evenly
+/// over each block
+fn tracked_minutes(blocks: &[ActivityBlock], range: (DateTime<Utc>, DateTime<Utc>)) -> f64 {
+    blocks
+        .iter()
+        .map(|b| {
+            let length = (b.block_end - b.block_start).num_seconds() as f64 / 60.0;
+            if length <= 0.0 {
+                return 0.0;
+            }
+            let share = overlap_minutes((b.block_start, b.block_end), range) / length;
+            b.active_seconds / 60.0 * share
+        })
+        .sum()
+}
+
+/// Time right after a plan where activity still counts towards it: to the end
+/// of the block the plan ends in (the next block, when it ends on a boundary),
+/// but never into the next plan
+fn overrun_window(
+    end: DateTime<Utc>,
+    plans: &[(DateTime<Utc>, DateTime<Utc>)],
+    blocks: &[ActivityBlock],
+) -> (DateTime<Utc>, DateTime<Utc>) {
+    let block_end = blocks
+        .iter()
+        .map(|b| b.block_end)
+        .filter(|block_end| *block_end > end)
+        .min()
+        .unwrap_or(end);
+    let next_plan = plans
+        .iter()
+        .map(|(start, _)| *start)
+        .filter(|start| *start >= end)
+        .min()
+        .unwrap_or(block_end);
+    (end, block_end.min(next_plan))
+}
+
+fn day_metrics(
+    conn: &Connection,
+    events: &[CalendarEvent],
+    blocks: &[ActivityBlock],
+    day: (DateTime<Utc>, DateTime<Utc>),
+    tz: &Tz,
+) -> rusqlite::Result<DayMetrics> {
+    let plans: Vec<(DateTime<Utc>, DateTime<Utc>)> = events
+        .iter()
+        .filter(|ev| !ev.is_all_day())
+        .filter_map(|ev| ev.time_range(tz))
+        .map(|(start, end)| (start.max(day.0), end.min(day.1)))
+        .filter(|(start, end)| start < end)
+        .collect();
+    // Tracked/planned ratio within each plan, and with the activity that ran on after it
+    let ratios: Vec<(f64, f64)> = plans
+        .iter()
+        .map(|(start, end)| {
+            let planned = (*end - *start).num_seconds() as f64 / 60.0;
+            let within = tracked_minutes(blocks, (*start, *end));
+            let after = tracked_minutes(blocks, overrun_window(*end, &plans, blocks));
+            (within / planned, (within + after) / planned)
+        })
+        .collect();
+    let completed = ratios.iter().filter(|(within, _)| *within >= COMPLETED_RATIO).count();
+
+    let minutes_of = |productivity: Productivity| -> f64 {
+        blocks
+            .iter()
+            .flat_map(|b| &b.categories)
+            .filter(|c| c.productivity == productivity)
+            .map(|c| c.seconds / 60.0)
+            .sum()
     };
+    let productive = minutes_of(Productivity::Productive);
+    let distracting = minutes_of(Productivity::Distracting);
+
+    let total_distractions = conn.query_row(
+        "SELECT COUNT(*) FROM distractions
+         WHERE user_id = ?1 AND start_time >= ?2 AND start_time < ?3",
+        params![active_user_id(conn), day.0.to_rfc3339(), day.1.to_rfc3339()],
+        |row| row.get(0),
+    )?;
+    let total_pomodoros = conn.query_row(
+        "SELECT COUNT(*) FROM pomodoro_sessions s
+         JOIN events e ON e.id = s.event_id
+         WHERE e.user_id = ?1 AND s.completed = 1 AND s.start_time >= ?2 AND s.start_time < ?3",
+        params![active_user_id(conn), day.0.to_rfc3339(), day.1.to_rfc3339()],
+        |row| row.get(0),
+    )?;
 
-    let resp = client.post("http://localhost:11434/api/generate").json(&payload).send().await.map_err(|e| e.to_string())?;
-    let data: OllamaResponse = resp.json().await.map_err(|e| e.to_string())?;
+    let planned_vs_actual = if plans.is_empty() {
+        0.0
+    } else {
+        completed as f64 / plans.len() as f64 * 100.0
+    };
 
-    Ok(data.response.replace("\\n", "\n").trim().to_string())
+    Ok(DayMetrics {
+        total_planned: plans.len() as i64,
+        total_completed: completed as i64,
+        total_pomodoros,
+        total_distractions,
+        reality_score: planned_vs_actual.round() as i64,
+        reality: RealityCheck {
+            planned_vs_actual,
+            distractions_detected: blocks
+                .iter()
+                .filter(|b| {
+                    b.categories
+                        .iter()
+                        .any(|c| c.productivity == Productivity::Distracting && c.seconds > 0.0)
+                })
+                .count(),
+            time_overruns: ratios.iter().filter(|(_, total)| *total > OVERRUN_RATIO).count(),
+            focus_score: if productive + distracting > 0.0 {
+                productive / (productive + distracting) * 100.0
+            } else {
+                0.0
+            },
+            afk_minutes: blocks.iter().map(|b| b.afk_seconds / 60.0).sum(),
+        },
+    })
 }
 
-/// Main function callable from frontend
+/// `summary_text` holds the review as JSON. Summaries stored as plain text
+/// before reviews were structured come back as a review without suggestions.
+fn summary_from_row(row: &Row) -> rusqlite::Result<StoredSummary> {
+    let reality_score: i64 = row.get(5)?;
+    let total_distractions: i64 = row.get(4)?;
+    let text = row.get::<_, Option<String>>(6)?.unwrap_or_default();
+    let review = serde_json::from_str(&text).unwrap_or_else(|_| DayReview {
+        summary: text,
+        strategy: String::new(),
+        reality_check: RealityCheck {
+            planned_vs_actual: reality_score as f64,
+            distractions_detected: total_distractions as usize,
+            time_overruns: 0,
+            focus_score: 0.0,
+            afk_minutes: 0.0,
+        },
+        suggestions: Vec::new(),
+    });
+
+    Ok(StoredSummary {
+        date: row.get(0)?,
+        total_planned: row.get(1)?,
+        total_completed: row.get(2)?,
+        total_pomodoros: row.get(3)?,
+        total_distractions,
+        reality_score,
+        review,
+        created_at: row.get(7)?,
+        updated_at: row.get(8)?,
+    })
+}
+
+const SUMMARY_COLUMNS: &str = "date, total_planned, total_completed, total_pomodoros, \
+    total_distractions, reality_score, summary_text, created_at, updated_at";
+
+fn stored_summary(conn: &Connection, date: NaiveDate) -> rusqlite::Result<Option<StoredSummary>> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM daily_summary WHERE user_id = ?1 AND date = ?2",
+            SUMMARY_COLUMNS
+        ),
+        params![active_user_id(conn), date.format("%Y-%m-%d").to_string()],
+        summary_from_row,
+    )
+    .optional()
+}
+
+/// Whether the RFC3339 `timestamp` is at or after `t`
+fn written_after(timestamp: &str, t: DateTime<Utc>) -> bool {
+    DateTime::parse_from_rfc3339(timestamp).is_ok_and(|at| at >= t)
+}
+
+/// Insert or replace the summary of `date`
+fn save_summary(
+    conn: &Connection,
+    date: NaiveDate,
+    metrics: &DayMetrics,
+    review: &DayReview,
+) -> rusqlite::Result<()> {
+    let to_sql_error = |e: serde_json::Error| rusqlite::Error::ToSqlConversionFailure(Box::new(e));
+    let summary_text = serde_json::to_string(review).map_err(to_sql_error)?;
+    // Kept on their own as well so they can be queried without the review
+    let suggestions_text = serde_json::to_string(&review.suggestions).map_err(to_sql_error)?;
+    let now = Utc::now().to_rfc3339();
+    let date = date.format("%Y-%m-%d").to_string();
+    let updated = conn.execute(
+        "UPDATE daily_summary SET
+            total_planned = ?3, total_completed = ?4, total_pomodoros = ?5,
+            total_distractions = ?6, reality_score = ?7, summary_text = ?8,
+            suggestions_text = ?9, updated_at = ?10
+         WHERE user_id = ?1 AND date = ?2",
+        params![
+            active_user_id(conn),
+            date,
+            metrics.total_planned,
+            metrics.total_completed,
+            metrics.total_pomodoros,
+            metrics.total_distractions,
+            metrics.reality_score,
+            summary_text,
+            suggestions_text,
+            now
+        ],
+    )?;
+    if updated == 0 {
+        conn.execute(
+            "INSERT INTO daily_summary (
+                user_id, date, total_planned, total_completed, total_pomodoros,
+                total_distractions, reality_score, summary_text, suggestions_text,
+                created_at, updated_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?10)",
+            params![
+                active_user_id(conn),
+                date,
+                metrics.total_planned,
+                metrics.total_completed,
+                metrics.total_pomodoros,
+                metrics.total_distractions,
+                metrics.reality_score,
+                summary_text,
+                suggestions_text,
+                now
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+/// Shape of the answer, spelled out in the prompt
+const REVIEW_SCHEMA: &str = r#"{
+  "summary": string, what actually happened compared to the plan,
+  "strategy": string, the brutal strategy for doing better,
+  "suggestions": [
+    { "title": string, "description": string, "priority": "High" | "Medium" | "Low" }
+  ] (1 to 5 items, the fixes for tomorrow)
+}"#;
+
+/// Checks serde can't express
+fn validate_review(review: &ReviewAnswer) -> Result<(), String> {
+    if review.summary.trim().is_empty() {
+        return Err("summary must not be empty".into());
+    }
+    if review.suggestions.is_empty() || review.suggestions.len() > MAX_SUGGESTIONS {
+        return Err(format!(
+            "suggestions must hold 1 to {} items, got {}",
+            MAX_SUGGESTIONS,
+            review.suggestions.len()
+        ));
+    }
+    if let Some(s) = review
+        .suggestions
+        .iter()
+        .find(|s| s.title.trim().is_empty() || s.description.trim().is_empty())
+    {
+        return Err(format!(
+            "every suggestion needs a title and a description, got {:?}",
+            s
+        ));
+    }
+    Ok(())
+}
+
+/// Review without a model: the template digest of the logs and the measured numbers
+async fn template_review(
+    client: &Client,
+    raw_text: &str,
+    reality: &RealityCheck,
+) -> Result<DayReview, String> {
+    let request = LlmRequest::new(raw_text.to_string(), raw_text);
+    Ok(DayReview {
+        summary: TemplateProvider.generate(client, &request).await?,
+        strategy: String::new(),
+        reality_check: reality.clone(),
+        suggestions: Vec::new(),
+    })
+}
+
+/// Review the logs with the configured LLM, as JSON checked against `REVIEW_SCHEMA`,
+/// keeping the measured `reality` as the reality check. Providers without JSON
+/// output, and models that keep answering with invalid JSON, get the template
+/// review instead; the flag is `false` then, since that review is only a stand-in.
+async fn review_day(
+    client: &Client,
+    raw_text: &str,
+    label: &str,
+    reality: &RealityCheck,
+) -> Result<(DayReview, bool), String> {
+    let provider = active_provider()?;
+    if !provider.supports_json() {
+        return Ok((template_review(client, raw_text, reality).await?, false));
+    }
+
+    let measured = serde_json::to_string_pretty(reality).map_err(|e| e.to_string())?;
+    let prompt = format!(
+        "You are a brutally honest productivity coach.\n\nReview the logs for {}:\n{}\n\nMeasured numbers (facts, don't second-guess them):\n{}\n\nAnswer with only a JSON object of this shape:\n{}",
+        label, raw_text, measured, REVIEW_SCHEMA
+    );
+
+    match generate_json(client, provider.as_ref(), prompt, raw_text, validate_review).await {
+        Ok(ReviewAnswer { summary, strategy, suggestions }) => Ok((
+            DayReview {
+                summary,
+                strategy,
+                reality_check: reality.clone(),
+                suggestions,
+            },
+            true,
+        )),
+        Err(e) if is_invalid_json(&e) => {
+            eprintln!("⚠️ Falling back to the template review: {}", e);
+            Ok((template_review(client, raw_text, reality).await?, false))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Main function callable from frontend. Covers `date` (`YYYY-MM-DD`, today by
+/// default) or, with `end_date`, every day up to and including it.
+///
+/// Single days are summarized once they are over and stored; pass `regenerate`
+/// to replace the stored summary. Today, and ranges, are always generated
+/// fresh and not stored, since their numbers are still changing.
 #[tauri::command]
-pub async fn get_daily_summary() -> Result<String, String> {
+pub async fn get_daily_summary(
+    date: Option<String>,
+    end_date: Option<String>,
+    regenerate: Option<bool>,
+) -> Result<DayReview, String> {
     let client = Client::new();
+    let tz = user_timezone();
+    let (first, last) = date_span(&tz, date, end_date)?;
+    let (start, _) = day_range(&tz, first);
+    let (_, end) = day_range(&tz, last);
+    let storable = first == last && end <= Utc::now();
+
+    if storable && !regenerate.unwrap_or(false) {
+        let conn = database::connection();
+        let stored = stored_summary(&conn, first).map_err(|e| e.to_string())?;
+        // Rows written while the day was still going were provisional
+        if let Some(stored) = stored.filter(|s| written_after(&s.updated_at, end)) {
+            return Ok(stored.review);
+        }
+    }
 
-    // Get token
-    let token = crate::auth::get_latest_token().map_err(|e| format!("Token error: {}", e))?;
+    let label = span_label(&tz, first, last);
 
-    // Check expiry
-    if Utc::now() > token.expiry_date {
-        return Err("Access token expired".into());
+    // Plans (token is refreshed if needed) and whatever activity was recorded
+    let events = load_plan_events(&client, start, end).await?;
+    let blocks = {
+        let conn = database::connection();
+        store::blocks_between(&conn, start, end).map_err(|e| e.to_string())?
+    };
+
+    let metrics = {
+        let conn = database::connection();
+        day_metrics(&conn, &events, &blocks, (start, end), &tz).map_err(|e| e.to_string())?
+    };
+
+    if events.is_empty() && blocks.is_empty() {
+        return Ok(DayReview {
+            summary: format!("❌ No events found for {}.", label),
+            strategy: String::new(),
+            reality_check: metrics.reality,
+            suggestions: Vec::new(),
+        });
     }
 
-    // Get today's events
-    let (start, end) = today_range_utc();
-    let events = get_calendar_events(&client, &token.access_token, start, end).await?;
+    let mut raw_logs = format!("Planned:\n{}", collect_descriptions(&events, &tz));
+    if !blocks.is_empty() {
+        raw_logs.push_str(&format!("\n\nTracked activity:\n{}", collect_activity(&blocks, &tz)));
+    }
+    let (review, reviewed) = review_day(&client, &raw_logs, &label, &metrics.reality).await?;
 
-    if events.is_empty() {
-        return Ok("❌ No events found for today.".into());
+    // A template stand-in isn't stored, so the next call asks the model again
+    if storable && reviewed {
+        let conn = database::connection();
+        save_summary(&conn, first, &metrics, &review).map_err(|e| e.to_string())?;
     }
 
-    let raw_logs = collect_descriptions(&events);
-    let summary = summarize_day(&client, &raw_logs).await?;
+    Ok(review)
+}
 
-    Ok(summary)
-}
\ No newline at end of file
+/// Stored daily summaries, newest first. `start_date`/`end_date`
+/// (`YYYY-MM-DD`, inclusive) narrow the history.
+#[tauri::command]
+pub fn get_summary_history(
+    start_date: Option<String>,
+    end_date: Option<String>,
+    limit: Option<u32>,
+) -> Result<Vec<StoredSummary>, String> {
+    // Validate, then compare as the stored `YYYY-MM-DD` strings
+    for d in start_date.iter().chain(&end_date) {
+        parse_date(d)?;
+    }
+    let conn = database::connection();
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM daily_summary
+             WHERE user_id = ?1
+               AND (?2 IS NULL OR date >= ?2)
+               AND (?3 IS NULL OR date <= ?3)
+             ORDER BY date DESC
+             LIMIT ?4",
+            SUMMARY_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map(
+            params![active_user_id(&conn), start_date, end_date, limit.unwrap_or(30)],
+            summary_from_row,
+        )
+        .map_err(|e| e.to_string())?;
+    rows.collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrations;
+    use serde_json::json;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    fn plan(id: &str, start: &str, end: &str) -> CalendarEvent {
+        serde_json::from_value(json!({
+            "id": id,
+            "start": { "dateTime": start },
+            "end": { "dateTime": end },
+        }))
+        .unwrap()
+    }
+
+    /// An hour block from `start`, active for `active_minutes`
+    fn block(start: &str, active_minutes: f64) -> ActivityBlock {
+        let start = utc(start);
+        serde_json::from_value(json!({
+            "id": 0,
+            "block_start": start,
+            "block_end": start + chrono::Duration::hours(1),
+            "title": "Work",
+            "raw_text": "",
+            "llm_summary": null,
+            "app_durations": {},
+            "active_seconds": active_minutes * 60.0,
+            "afk_seconds": 0.0,
+            "sync_status": "synced",
+            "sync_error": null,
+            "calendar_event_id": null,
+            "summarized_at": null,
+            "categories": [],
+        }))
+        .unwrap()
+    }
+
+    fn overruns(events: &[CalendarEvent], blocks: &[ActivityBlock]) -> usize {
+        let conn = Connection::open_in_memory().unwrap();
+        migrations::migrate(&conn).unwrap();
+        let tz: Tz = "UTC".parse().unwrap();
+        let day = (utc("2024-05-02T00:00:00Z"), utc("2024-05-03T00:00:00Z"));
+        day_metrics(&conn, events, blocks, day, &tz)
+            .unwrap()
+            .reality
+            .time_overruns
+    }
+
+    #[test]
+    fn work_running_on_after_a_plan_is_an_overrun() {
+        let events = [plan("a", "2024-05-02T09:00:00Z", "2024-05-02T10:00:00Z")];
+        let blocks = [block("2024-05-02T09:00:00Z", 60.0), block("2024-05-02T10:00:00Z", 45.0)];
+        assert_eq!(overruns(&events, &blocks), 1);
+    }
+
+    #[test]
+    fn plan_finished_on_time_is_no_overrun() {
+        let events = [plan("a", "2024-05-02T09:00:00Z", "2024-05-02T10:00:00Z")];
+        let blocks = [block("2024-05-02T09:00:00Z", 60.0), block("2024-05-02T10:00:00Z", 5.0)];
+        assert_eq!(overruns(&events, &blocks), 0);
+    }
+
+    #[test]
+    fn activity_of_the_next_plan_is_no_overrun() {
+        let events = [
+            plan("a", "2024-05-02T09:00:00Z", "2024-05-02T10:00:00Z"),
+            plan("b", "2024-05-02T10:00:00Z", "2024-05-02T11:00:00Z"),
+        ];
+        let blocks = [block("2024-05-02T09:00:00Z", 60.0), block("2024-05-02T10:00:00Z", 60.0)];
+        assert_eq!(overruns(&events, &blocks), 0);
+    }
+}
@@ -1,10 +1,11 @@
 use once_cell::sync::Lazy;
-use rusqlite::{Connection, Result};
+use rusqlite::Connection;
 use std::path::PathBuf;
 use std::sync::Mutex;
 use std::fs;
 use super::seeder; 
-use super::schema; // bring in schema.rs
+use super::migrations;
+use crate::secrets;
 
 static DB_CONNECTION: Lazy<Mutex<Connection>> = Lazy::new(|| {
     let database_path = get_app_data_dir().join("database.db");
@@ -33,12 +34,16 @@ pub fn connection() -> std::sync::MutexGuard<'static, Connection> {
     DB_CONNECTION.lock().expect("❌ Failed to lock DB")
 }
 
-pub fn init() -> Result<()> {
+pub fn init() -> Result<(), String> {
     let conn = connection();
 
     // Run migrations
-    conn.execute_batch(&schema::create_all_sql())?;
-    println!("✅ Database initialized with full schema");
+    migrations::migrate(&conn)?;
+    secrets::verify_key(&conn)?;
+    println!(
+        "✅ Database initialized at schema version {}",
+        migrations::latest_version()
+    );
 
     // Seed initial data (only if empty)
     if let Err(e) = seeder::seed_credentials(&conn) {
@@ -47,5 +52,9 @@ pub fn init() -> Result<()> {
         println!("✅ Credentials seeded (dummy values)");
     }
 
+    if let Err(e) = seeder::seed_category_rules(&conn) {
+        eprintln!("⚠️ Seeding category rules failed: {}", e);
+    }
+
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,433 @@
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde_json::Value;
+
+use crate::secrets;
+
+/// The schema as it was when versioning started. Frozen: changes to it are
+/// new migrations.
+const BASELINE_SQL: &str = "
+CREATE TABLE IF NOT EXISTS users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    email TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    description TEXT,
+    category TEXT,
+    planned_start TEXT NOT NULL,
+    planned_end TEXT NOT NULL,
+    actual_start TEXT,
+    actual_end TEXT,
+    auto_detected_start TEXT,
+    status TEXT DEFAULT 'planned',
+    distraction_flag INTEGER DEFAULT 0,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY(user_id) REFERENCES users(id)
+);
+
+CREATE TABLE IF NOT EXISTS pomodoro_sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    event_id INTEGER NOT NULL,
+    start_time TEXT NOT NULL,
+    end_time TEXT,
+    paused_duration INTEGER DEFAULT 0,
+    distraction_count INTEGER DEFAULT 0,
+    interval_number INTEGER NOT NULL,
+    completed INTEGER DEFAULT 0,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY(event_id) REFERENCES events(id)
+);
+
+CREATE TABLE IF NOT EXISTS daily_summary (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    date TEXT NOT NULL,
+    total_planned INTEGER DEFAULT 0,
+    total_completed INTEGER DEFAULT 0,
+    total_pomodoros INTEGER DEFAULT 0,
+    total_distractions INTEGER DEFAULT 0,
+    reality_score INTEGER DEFAULT 0,
+    summary_text TEXT,
+    suggestions_text TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY(user_id) REFERENCES users(id)
+);
+
+CREATE TABLE IF NOT EXISTS distractions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    event_id INTEGER,
+    user_id INTEGER NOT NULL,
+    start_time TEXT NOT NULL,
+    end_time TEXT,
+    type TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY(event_id) REFERENCES events(id),
+    FOREIGN KEY(user_id) REFERENCES users(id)
+);
+
+CREATE TABLE IF NOT EXISTS credentials (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    provider TEXT NOT NULL DEFAULT 'google',
+    owner TEXT NOT NULL DEFAULT 'system', -- 'system' (your app) or 'user' (if user provides their own)
+    client_id TEXT NOT NULL,
+    project_id TEXT NOT NULL,
+    auth_uri TEXT NOT NULL,
+    token_uri TEXT NOT NULL,
+    auth_provider_x509_cert_url TEXT NOT NULL,
+    client_secret TEXT NOT NULL,
+    redirect_uris TEXT NOT NULL, -- JSON array string
+    scopes TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS calendar_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    credential_id INTEGER NOT NULL,
+    access_token TEXT NOT NULL,
+    refresh_token TEXT,
+    scope TEXT,
+    token_type TEXT,
+    expiry_date TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS activity_blocks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    block_start TEXT NOT NULL,
+    block_end TEXT NOT NULL,
+    title TEXT NOT NULL,
+    raw_text TEXT,
+    llm_summary TEXT,
+    app_durations TEXT NOT NULL DEFAULT '{}', -- JSON object: app -> seconds
+    active_seconds REAL DEFAULT 0,
+    afk_seconds REAL DEFAULT 0,
+    sync_status TEXT DEFAULT 'pending', -- 'pending', 'synced' or 'failed'
+    sync_error TEXT,
+    calendar_event_id TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    UNIQUE(user_id, block_start, block_end),
+    FOREIGN KEY(user_id) REFERENCES users(id)
+);
+
+CREATE TABLE IF NOT EXISTS calendar_outbox (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    activity_block_id INTEGER NOT NULL UNIQUE,
+    attempts INTEGER DEFAULT 0,
+    next_attempt_at TEXT NOT NULL,
+    last_error TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY(activity_block_id) REFERENCES activity_blocks(id)
+);
+
+CREATE TABLE IF NOT EXISTS category_rules (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    match_field TEXT NOT NULL, -- 'app', 'title' (regex) or 'domain'
+    pattern TEXT NOT NULL,
+    category TEXT NOT NULL,
+    productivity TEXT NOT NULL DEFAULT 'neutral', -- 'productive', 'neutral' or 'distracting'
+    priority INTEGER NOT NULL DEFAULT 0, -- higher wins when several rules match
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS activity_block_categories (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    activity_block_id INTEGER NOT NULL,
+    category TEXT NOT NULL,
+    productivity TEXT NOT NULL,
+    seconds REAL NOT NULL DEFAULT 0,
+    FOREIGN KEY(activity_block_id) REFERENCES activity_blocks(id)
+);
+
+CREATE TABLE IF NOT EXISTS user_calendars (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    calendar_id TEXT NOT NULL,
+    summary TEXT,
+    role TEXT NOT NULL, -- 'activity' (write target, one per user) or 'plan' (read)
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    UNIQUE(user_id, calendar_id, role),
+    FOREIGN KEY(user_id) REFERENCES users(id)
+);
+
+CREATE TABLE IF NOT EXISTS calendar_event_cache (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    calendar_id TEXT NOT NULL,
+    event_id TEXT NOT NULL,
+    start_time TEXT NOT NULL, -- UTC, for range queries
+    end_time TEXT NOT NULL,
+    data TEXT NOT NULL, -- the event as JSON
+    updated_at TEXT NOT NULL,
+    UNIQUE(user_id, calendar_id, event_id),
+    FOREIGN KEY(user_id) REFERENCES users(id)
+);
+
+CREATE TABLE IF NOT EXISTS calendar_sync_state (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    calendar_id TEXT NOT NULL,
+    sync_token TEXT NOT NULL,
+    synced_from TEXT NOT NULL, -- the cache holds every event ending after this
+    updated_at TEXT NOT NULL,
+    UNIQUE(user_id, calendar_id),
+    FOREIGN KEY(user_id) REFERENCES users(id)
+);
+
+CREATE TABLE IF NOT EXISTS reports (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    kind TEXT NOT NULL, -- 'weekly' or 'monthly'
+    period_start TEXT NOT NULL, -- first day, YYYY-MM-DD
+    period_end TEXT NOT NULL, -- last day (inclusive)
+    metrics TEXT NOT NULL, -- JSON
+    narrative TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    UNIQUE(user_id, kind, period_start),
+    FOREIGN KEY(user_id) REFERENCES users(id)
+);
+";
+
+/// One forward-only schema change
+struct Migration {
+    description: &'static str,
+    up: fn(&Transaction) -> rusqlite::Result<()>,
+}
+
+/// Every schema change in order; the schema version is the number applied.
+/// Append new migrations at the end and never edit one that has shipped.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        description: "baseline schema",
+        // Installs from before versioning already have (some of) these tables
+        up: |tx| tx.execute_batch(BASELINE_SQL),
+    },
+    Migration {
+        description: "one daily summary per user and day",
+        up: |tx| {
+            tx.execute_batch(
+                "DELETE FROM daily_summary WHERE id NOT IN (
+                    SELECT MAX(id) FROM daily_summary GROUP BY user_id, date
+                );
+                CREATE UNIQUE INDEX IF NOT EXISTS idx_daily_summary_user_date
+                    ON daily_summary(user_id, date);",
+            )
+        },
+    },
+    Migration {
+        description: "encrypt stored OAuth secrets",
+        up: |tx| {
+            encrypt_column(tx, "calendar_tokens", "access_token")?;
+            encrypt_column(tx, "calendar_tokens", "refresh_token")?;
+            encrypt_column(tx, "credentials", "client_secret")?;
+            encrypt_llm_api_key(tx)
+        },
+    },
+    Migration {
+        description: "record when activity blocks were summarized",
+        // Until now a stored block was never summarized again
+        up: |tx| {
+            tx.execute_batch(
+                "ALTER TABLE activity_blocks ADD COLUMN summarized_at TEXT;
+                UPDATE activity_blocks SET summarized_at = created_at;",
+            )
+        },
+    },
+];
+
+/// Encrypt the plaintext values of a secret column in place
+fn encrypt_column(tx: &Transaction, table: &str, column: &str) -> rusqlite::Result<()> {
+    let rows = {
+        let mut stmt = tx.prepare(&format!(
+            "SELECT id, {column} FROM {table} WHERE {column} IS NOT NULL"
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()?
+    };
+
+    for (id, value) in rows {
+        if secrets::is_encrypted(&value) {
+            continue;
+        }
+        let encrypted = secrets::encrypt(&value)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+        tx.execute(
+            &format!("UPDATE {table} SET {column} = ?1 WHERE id = ?2"),
+            params![encrypted, id],
+        )?;
+    }
+    Ok(())
+}
+
+/// Encrypt the `api_key` in the JSON of the `llm` setting, working on the
+/// stored JSON so the migration doesn't depend on today's `LlmSettings`
+fn encrypt_llm_api_key(tx: &Transaction) -> rusqlite::Result<()> {
+    let json: Option<String> = tx
+        .query_row("SELECT value FROM settings WHERE key = 'llm'", [], |row| row.get(0))
+        .optional()?;
+    let Some(mut llm) = json.and_then(|json| serde_json::from_str::<Value>(&json).ok()) else {
+        return Ok(());
+    };
+    let Some(api_key) = llm.get("api_key").and_then(|key| key.as_str()) else {
+        return Ok(());
+    };
+    if secrets::is_encrypted(api_key) {
+        return Ok(());
+    }
+
+    let encrypted = secrets::encrypt(api_key)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+    llm["api_key"] = encrypted.into();
+    tx.execute(
+        "UPDATE settings SET value = ?1 WHERE key = 'llm'",
+        params![llm.to_string()],
+    )?;
+    Ok(())
+}
+
+/// Schema version this build writes
+pub fn latest_version() -> i64 {
+    MIGRATIONS.len() as i64
+}
+
+fn user_version(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+/// Apply every migration newer than the database's `user_version`, each in
+/// its own transaction together with the version bump. Refuses databases
+/// written by a newer build, whose schema this one can't know.
+pub fn migrate(conn: &Connection) -> Result<(), String> {
+    let current = user_version(conn).map_err(|e| e.to_string())?;
+    let latest = latest_version();
+
+    if current > latest {
+        return Err(format!(
+            "Database schema version {} is newer than this version of Anthyre supports ({}). Please update Anthyre.",
+            current, latest
+        ));
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(current as usize) {
+        let version = index as i64 + 1;
+        let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+        (migration.up)(&tx)
+            .and_then(|_| tx.execute_batch(&format!("PRAGMA user_version = {}", version)))
+            .and_then(|_| tx.commit())
+            .map_err(|e| {
+                format!(
+                    "Migration {} ({}) failed: {}",
+                    version, migration.description, e
+                )
+            })?;
+        println!(
+            "✅ Migrated database to version {}: {}",
+            version, migration.description
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column_exists(conn: &Connection, table: &str, column: &str) -> bool {
+        conn.prepare(&format!("SELECT {column} FROM {table}")).is_ok()
+    }
+
+    #[test]
+    fn new_database_reaches_latest_version() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate(&conn).unwrap();
+        assert_eq!(user_version(&conn).unwrap(), latest_version());
+        assert!(column_exists(&conn, "activity_blocks", "summarized_at"));
+    }
+
+    #[test]
+    fn migrating_twice_changes_nothing() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate(&conn).unwrap();
+        migrate(&conn).unwrap();
+        assert_eq!(user_version(&conn).unwrap(), latest_version());
+    }
+
+    #[test]
+    fn unversioned_install_is_upgraded() {
+        // Tables created before versioning, with the duplicate summaries it allowed
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE daily_summary (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id INTEGER NOT NULL,
+                date TEXT NOT NULL,
+                total_planned INTEGER DEFAULT 0,
+                total_completed INTEGER DEFAULT 0,
+                total_pomodoros INTEGER DEFAULT 0,
+                total_distractions INTEGER DEFAULT 0,
+                reality_score INTEGER DEFAULT 0,
+                summary_text TEXT,
+                suggestions_text TEXT,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+            INSERT INTO daily_summary (user_id, date, summary_text, created_at, updated_at) VALUES
+                (1, '2024-05-02', 'old', '', ''),
+                (1, '2024-05-02', 'new', '', ''),
+                (1, '2024-05-03', 'other', '', '');",
+        )
+        .unwrap();
+
+        migrate(&conn).unwrap();
+
+        assert_eq!(user_version(&conn).unwrap(), latest_version());
+        let summaries: Vec<String> = conn
+            .prepare("SELECT summary_text FROM daily_summary ORDER BY date")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(summaries, ["new", "other"]);
+    }
+
+    #[test]
+    fn newer_schema_is_refused() {
+        let conn = Connection::open_in_memory().unwrap();
+        let newer = latest_version() + 1;
+        conn.execute_batch(&format!("PRAGMA user_version = {}", newer)).unwrap();
+
+        let err = migrate(&conn).unwrap_err();
+        assert!(err.contains("newer"), "{}", err);
+        assert_eq!(user_version(&conn).unwrap(), newer);
+        assert!(!column_exists(&conn, "users", "id"));
+    }
+}
@@ -1,5 +1,6 @@
 pub mod db;
-pub mod schema;
+pub mod migrations;
 pub mod seeder;
+pub mod settings;
 
 pub use db::{init, connection, get_app_data_dir as anthyre_dir};
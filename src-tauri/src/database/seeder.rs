@@ -1,6 +1,8 @@
 use chrono::Utc;
 use rusqlite::{params, Connection, Result};
 
+use crate::secrets;
+
 /// Check if a table is empty
 pub fn is_table_empty(conn: &Connection, table: &str) -> Result<bool> {
     let mut stmt = conn.prepare(&format!("SELECT COUNT(*) FROM {}", table))?;
@@ -20,6 +22,8 @@ pub fn seed_credentials(conn: &Connection) -> Result<()> {
             std::env::var("GOOGLE_PROJECT_ID").unwrap_or_else(|_| "CHANGE_ME_PROJECT_ID".into());
         let client_secret = std::env::var("GOOGLE_CLIENT_SECRET")
             .unwrap_or_else(|_| "CHANGE_ME_CLIENT_SECRET".into());
+        let client_secret = secrets::encrypt(&client_secret)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
         let redirect_uris = std::env::var("GOOGLE_REDIRECT_URI")
             .unwrap_or_else(|_| r#"["http://localhost:1421/oauth2callback"]"#.into());
         let scopes = std::env::var("GOOGLE_SCOPES").unwrap_or_else(|_| {
@@ -59,3 +63,47 @@ pub fn seed_credentials(conn: &Connection) -> Result<()> {
 
     Ok(())
 }
+
+/// Seed a starter set of category rules (only if there are none yet)
+pub fn seed_category_rules(conn: &Connection) -> Result<()> {
+    if !is_table_empty(conn, "category_rules")? {
+        return Ok(());
+    }
+
+    // (match_field, pattern, category, productivity)
+    let rules = [
+        ("app", "code", "Deep Work", "productive"),
+        ("app", "idea64", "Deep Work", "productive"),
+        ("app", "windowsterminal", "Deep Work", "productive"),
+        ("app", "gnome-terminal-server", "Deep Work", "productive"),
+        ("app", "slack", "Comms", "neutral"),
+        ("app", "discord", "Comms", "neutral"),
+        ("app", "ms-teams", "Comms", "neutral"),
+        ("app", "outlook", "Comms", "neutral"),
+        ("domain", "github.com", "Deep Work", "productive"),
+        ("domain", "stackoverflow.com", "Deep Work", "productive"),
+        ("domain", "docs.rs", "Deep Work", "productive"),
+        ("domain", "mail.google.com", "Comms", "neutral"),
+        ("domain", "calendar.google.com", "Planning", "neutral"),
+        ("domain", "youtube.com", "Entertainment", "distracting"),
+        ("domain", "netflix.com", "Entertainment", "distracting"),
+        ("domain", "twitch.tv", "Entertainment", "distracting"),
+        ("domain", "reddit.com", "Entertainment", "distracting"),
+        ("domain", "x.com", "Entertainment", "distracting"),
+        ("domain", "instagram.com", "Entertainment", "distracting"),
+        ("title", "(?i)youtube|netflix|twitch", "Entertainment", "distracting"),
+    ];
+
+    let now = Utc::now().to_rfc3339();
+    for (match_field, pattern, category, productivity) in rules {
+        conn.execute(
+            "INSERT INTO category_rules (
+                match_field, pattern, category, productivity, priority, created_at, updated_at
+            ) VALUES (?1, ?2, ?3, ?4, 0, ?5, ?5)",
+            params![match_field, pattern, category, productivity, &now],
+        )?;
+    }
+    println!("✅ Seeded default category rules");
+
+    Ok(())
+}
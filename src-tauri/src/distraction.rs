@@ -0,0 +1,307 @@
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use reqwest::Client;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+
+use crate::activity::{
+    activitywatch::{current_window, AwEventData},
+    categorize::{Categorizer, Productivity},
+};
+use crate::database::{self, settings};
+use crate::pomodoro;
+use crate::timezone::{day_range, parse_date, today, user_timezone};
+use crate::users::active_user_id;
+
+/// "false" turns detection off; on by default
+const ENABLED_SETTING: &str = "distraction_detection";
+/// Emitted with a `DistractionAlert` when a distraction starts
+const DETECTED_EVENT: &str = "distraction-detected";
+const POLL_INTERVAL_SECS: u64 = 5;
+
+/// Something the user is meant to be focused on
+struct Focus {
+    event_id: i64,
+    /// Set while a pomodoro work interval runs
+    session_id: Option<i64>,
+}
+
+/// The `distractions` row still open
+struct OpenDistraction {
+    id: i64,
+    event_id: i64,
+    /// Pomodoro work interval it was counted against
+    session_id: Option<i64>,
+    kind: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct DistractionAlert {
+    pub id: i64,
+    pub event_id: i64,
+    /// Category of the matching distracting rule
+    pub kind: String,
+    pub app: Option<String>,
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub start_time: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Distraction {
+    pub id: i64,
+    pub event_id: Option<i64>,
+    pub kind: Option<String>,
+    pub start_time: String,
+    pub end_time: Option<String>,
+    /// `None` while the distraction is still going on
+    pub duration_seconds: Option<i64>,
+}
+
+static OPEN: Lazy<Mutex<Option<OpenDistraction>>> = Lazy::new(|| Mutex::new(None));
+
+fn is_enabled(conn: &Connection) -> bool {
+    settings::get(conn, ENABLED_SETTING)
+        .ok()
+        .flatten()
+        .map(|value| value != "false")
+        .unwrap_or(true)
+}
+
+/// The running pomodoro work interval, else an unfinished `events` row planned for now
+fn current_focus(conn: &Connection, work: Option<(i64, i64)>) -> rusqlite::Result<Option<Focus>> {
+    if let Some((event_id, session_id)) = work {
+        return Ok(Some(Focus {
+            event_id,
+            session_id: Some(session_id),
+        }));
+    }
+    let now = Utc::now().to_rfc3339();
+    conn.query_row(
+        "SELECT id FROM events
+         WHERE user_id = ?1 AND status != 'completed'
+           AND planned_start <= ?2 AND planned_end > ?2
+         ORDER BY planned_start DESC
+         LIMIT 1",
+        params![active_user_id(conn), now],
+        |row| row.get(0),
+    )
+    .optional()
+    .map(|id| {
+        id.map(|event_id| Focus {
+            event_id,
+            session_id: None,
+        })
+    })
+}
+
+/// Category of the distracting rule `data` matches, if any
+fn distraction_kind(categorizer: &Categorizer, data: &AwEventData) -> Option<String> {
+    categorizer
+        .categorize(data)
+        .filter(|rule| rule.productivity == Productivity::Distracting)
+        .map(|rule| rule.category.clone())
+}
+
+fn open_distraction(
+    conn: &Connection,
+    focus: &Focus,
+    kind: &str,
+    at: DateTime<Utc>,
+) -> rusqlite::Result<i64> {
+    let now = Utc::now().to_rfc3339();
+    conn.execute(
+        "INSERT INTO distractions (event_id, user_id, start_time, type, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+        params![focus.event_id, active_user_id(conn), at.to_rfc3339(), kind, now],
+    )?;
+    let id = conn.last_insert_rowid();
+
+    conn.execute(
+        "UPDATE events SET distraction_flag = 1, updated_at = ?2 WHERE id = ?1",
+        params![focus.event_id, now],
+    )?;
+    if let Some(session_id) = focus.session_id {
+        pomodoro::add_distraction(conn, session_id)?;
+    }
+    Ok(id)
+}
+
+fn close_distraction(conn: &Connection, id: i64, at: DateTime<Utc>) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE distractions SET end_time = ?2, updated_at = ?2 WHERE id = ?1",
+        params![id, at.to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+/// Mark an open distraction as still going on, so it can be closed at the
+/// right time if the app quits before it ends
+fn touch_distraction(conn: &Connection, id: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE distractions SET updated_at = ?2 WHERE id = ?1",
+        params![id, Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+/// Close distractions left open when the app last quit, at the last time they were seen
+fn close_dangling(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE distractions SET end_time = updated_at WHERE end_time IS NULL",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Compare the window in focus with the distraction rules and open or close
+/// `distractions` rows. Returns the distraction that just started, if any.
+async fn poll(client: &Client) -> Result<Option<DistractionAlert>, String> {
+    // Before the database: the pomodoro timer is always locked first
+    let work = pomodoro::active_work();
+    let (focus, categorizer) = {
+        let conn = database::connection();
+        if !is_enabled(&conn) {
+            (None, None)
+        } else {
+            let focus = current_focus(&conn, work).map_err(|e| e.to_string())?;
+            let categorizer = Categorizer::load(&conn).map_err(|e| e.to_string())?;
+            (focus, Some(categorizer))
+        }
+    };
+
+    let window = match (&focus, &categorizer) {
+        (Some(_), Some(_)) => current_window(client).await?,
+        _ => None,
+    };
+    let matched = window.as_ref().and_then(|window| {
+        let kind = distraction_kind(categorizer.as_ref()?, &window.data)?;
+        Some((kind, window.data.clone()))
+    });
+
+    let now = Utc::now();
+    let mut open = OPEN.lock().map_err(|e| e.to_string())?;
+    let conn = database::connection();
+
+    // Still the same distraction during the same focus; a new work interval
+    // gets a distraction of its own
+    if let (Some(current), Some(focus), Some((kind, _))) = (open.as_ref(), &focus, &matched) {
+        if current.event_id == focus.event_id
+            && current.session_id == focus.session_id
+            && current.kind == *kind
+        {
+            touch_distraction(&conn, current.id).map_err(|e| e.to_string())?;
+            return Ok(None);
+        }
+    }
+
+    if let Some(current) = open.take() {
+        close_distraction(&conn, current.id, now).map_err(|e| e.to_string())?;
+    }
+
+    let (Some(focus), Some((kind, data))) = (focus, matched) else {
+        return Ok(None);
+    };
+    let id = open_distraction(&conn, &focus, &kind, now).map_err(|e| e.to_string())?;
+    *open = Some(OpenDistraction {
+        id,
+        event_id: focus.event_id,
+        session_id: focus.session_id,
+        kind: kind.clone(),
+    });
+
+    Ok(Some(DistractionAlert {
+        id,
+        event_id: focus.event_id,
+        kind,
+        app: data.app,
+        title: data.title,
+        url: data.url,
+        start_time: now,
+    }))
+}
+
+/// Background loop watching the focused window during events and pomodoros
+pub async fn run(app: AppHandle) {
+    {
+        let conn = database::connection();
+        if let Err(e) = close_dangling(&conn) {
+            eprintln!("⚠️ Could not close open distractions: {}", e);
+        }
+    }
+
+    let client = Client::new();
+    loop {
+        match poll(&client).await {
+            Ok(Some(alert)) => {
+                println!("⚠️ Distraction: {} ({:?})", alert.kind, alert.app);
+                if let Err(e) = app.emit(DETECTED_EVENT, &alert) {
+                    eprintln!("⚠️ Could not emit {}: {}", DETECTED_EVENT, e);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("Distraction check failed: {}", e),
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(POLL_INTERVAL_SECS)).await;
+    }
+}
+
+/// Distractions of a day (`YYYY-MM-DD`, today by default), oldest first
+#[tauri::command]
+pub fn list_distractions(date: Option<String>) -> Result<Vec<Distraction>, String> {
+    let tz = user_timezone();
+    let date = match date {
+        Some(d) => parse_date(&d)?,
+        None => today(&tz),
+    };
+    let (start, end) = day_range(&tz, date);
+
+    let conn = database::connection();
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, event_id, type, start_time, end_time FROM distractions
+             WHERE user_id = ?1 AND start_time >= ?2 AND start_time < ?3
+             ORDER BY start_time",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map(
+            params![active_user_id(&conn), start.to_rfc3339(), end.to_rfc3339()],
+            |row| {
+                let start_time: String = row.get(3)?;
+                let end_time: Option<String> = row.get(4)?;
+                let parse = |t: &str| DateTime::parse_from_rfc3339(t).ok();
+                let duration_seconds = end_time
+                    .as_deref()
+                    .and_then(parse)
+                    .zip(parse(&start_time))
+                    .map(|(end, start)| (end - start).num_seconds());
+                Ok(Distraction {
+                    id: row.get(0)?,
+                    event_id: row.get(1)?,
+                    kind: row.get(2)?,
+                    start_time,
+                    end_time,
+                    duration_seconds,
+                })
+            },
+        )
+        .map_err(|e| e.to_string())?;
+    rows.collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_distraction_detection() -> bool {
+    let conn = database::connection();
+    is_enabled(&conn)
+}
+
+#[tauri::command]
+pub fn set_distraction_detection(enabled: bool) -> Result<(), String> {
+    let conn = database::connection();
+    settings::set(&conn, ENABLED_SETTING, &enabled.to_string()).map_err(|e| e.to_string())
+}
@@ -2,7 +2,13 @@ mod auth;
 mod database;
 mod activity;
 mod daily_report;
+mod distraction;
 mod llm;
+mod pomodoro;
+mod report;
+mod secrets;
+mod timezone;
+mod users;
 use crate::activity::processor::make_batches;
 
 use tauri::Manager;
@@ -23,23 +29,17 @@ pub fn run() {
                 eprintln!("⚠️ Seeding credentials failed: {}", e);
             }
 
-            // ✅ spawn background task using Tauri's runtime
-            let app_handle = app.handle().clone();
-            tauri::async_runtime::spawn(async move {
-                use tokio::time::{interval, Duration};
-                let mut ticker = interval(Duration::from_secs(3600)); // 1hr
-
-                loop {
-                    ticker.tick().await; // waits for next tick
-                    println!("🔄 Running scheduled update...");
-                    let res = crate::activity::update_hours().await;
-                    if let Err(e) = res {
-                        eprintln!("Background update error: {:?}", e);
-                    } else {
-                        println!("✅ Scheduled update completed successfully");
-                    }
-                }
-            });
+            // ✅ hourly updates, backfilling hours missed while closed or asleep
+            tauri::async_runtime::spawn(crate::activity::scheduler::run());
+
+            // ✅ retry calendar writes that failed while offline
+            tauri::async_runtime::spawn(crate::activity::outbox::run_worker());
+
+            // ✅ pomodoro ticks, picking up a timer left running before a restart
+            tauri::async_runtime::spawn(crate::pomodoro::run(app.handle().clone()));
+
+            // ✅ watch the focused window during events and pomodoros
+            tauri::async_runtime::spawn(crate::distraction::run(app.handle().clone()));
 
             // ✅ return correct type
             Ok::<(), Box<dyn std::error::Error>>(())
@@ -47,11 +47,52 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             auth::login_with_google,
             auth::check_calendar_token,
+            users::get_current_user,
+            users::list_users,
+            users::switch_user,
             activity::update_hours,
             activity::update_hours_range,
+            activity::store::get_activity_blocks,
+            activity::outbox::get_sync_queue_status,
+            activity::outbox::retry_sync_queue,
+            activity::scheduler::get_scheduler_status,
+            activity::scheduler::set_backfill_limit,
+            activity::granularity::get_block_minutes,
+            activity::granularity::set_block_minutes,
+            timezone::get_timezone,
+            timezone::set_timezone,
+            activity::categorize::list_category_rules,
+            activity::categorize::add_category_rule,
+            activity::categorize::update_category_rule,
+            activity::categorize::delete_category_rule,
+            activity::activitywatch::list_aw_hosts,
+            activity::activitywatch::select_aw_host,
             daily_report::get_daily_summary,
+            daily_report::get_summary_history,
+            report::generate_report,
+            report::get_report,
+            report::list_reports,
             activity::processor::fetch_batches,
+            activity::processor::fetch_batches_range,
+            activity::day_view::get_day_view,
+            activity::calendars::list_user_calendars,
+            activity::calendars::get_calendar_settings,
+            activity::calendars::set_activity_calendar,
+            activity::calendars::set_plan_calendars,
+            pomodoro::get_pomodoro_config,
+            pomodoro::set_pomodoro_config,
+            pomodoro::get_pomodoro_status,
+            pomodoro::start_pomodoro,
+            pomodoro::pause_pomodoro,
+            pomodoro::resume_pomodoro,
+            pomodoro::skip_pomodoro,
+            pomodoro::stop_pomodoro,
+            distraction::list_distractions,
+            distraction::get_distraction_detection,
+            distraction::set_distraction_detection,
             llm::ask_mistral,
+            llm::get_llm_settings,
+            llm::set_llm_settings,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
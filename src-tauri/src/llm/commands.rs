@@ -0,0 +1,46 @@
+use reqwest::Client;
+use tauri::Emitter;
+
+use super::provider::{active_provider, LlmRequest};
+use super::settings::{self as llm_settings, LlmSettings};
+use crate::database;
+
+/// Stands in for a stored API key in the settings sent to the webview
+const API_KEY_MASK: &str = "********";
+
+#[tauri::command]
+pub async fn ask_mistral(app_handle: tauri::AppHandle, prompt: String) -> Result<(), String> {
+    let client = Client::new();
+    let provider = active_provider()?;
+    let request = LlmRequest::new(prompt.clone(), &prompt);
+
+    let emit = |token: String| {
+        app_handle
+            .emit("llm-token", token)
+            .map_err(|e| e.to_string())
+    };
+    provider.stream(&client, &request, &emit).await
+}
+
+/// The LLM settings, with a stored API key masked: it never leaves the backend
+#[tauri::command]
+pub fn get_llm_settings() -> Result<LlmSettings, String> {
+    let conn = database::connection();
+    let mut settings = llm_settings::load(&conn).map_err(|e| e.to_string())?;
+    if settings.api_key.is_some() {
+        settings.api_key = Some(API_KEY_MASK.to_string());
+    }
+    Ok(settings)
+}
+
+/// Save the LLM settings. An empty or masked API key keeps the stored one.
+#[tauri::command]
+pub fn set_llm_settings(mut settings: LlmSettings) -> Result<(), String> {
+    if let None | Some("") | Some(API_KEY_MASK) = settings.api_key.as_deref() {
+        let conn = database::connection();
+        settings.api_key = llm_settings::load(&conn).map_err(|e| e.to_string())?.api_key;
+    }
+    super::provider::provider_from_settings(settings.clone())?;
+    let conn = database::connection();
+    llm_settings::save(&conn, &settings).map_err(|e| e.to_string())
+}
@@ -0,0 +1,10 @@
+pub mod commands;
+pub mod ollama;
+pub mod openai;
+pub mod provider;
+pub mod settings;
+pub mod structured;
+pub mod template;
+
+pub use commands::*;
+pub use provider::{active_provider, LlmRequest};
@@ -0,0 +1,165 @@
+use futures_util::future::BoxFuture;
+use futures_util::StreamExt;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use super::provider::{LlmProvider, LlmRequest, TokenSink};
+use super::settings::LlmSettings;
+
+pub struct OllamaProvider {
+    settings: LlmSettings,
+}
+
+#[derive(Serialize)]
+struct OllamaOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_predict: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct OllamaRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<&'a str>,
+    options: OllamaOptions,
+}
+
+#[derive(Deserialize)]
+struct OllamaResponse {
+    response: String,
+    #[serde(default)]
+    done: bool,
+}
+
+impl OllamaProvider {
+    pub fn new(settings: LlmSettings) -> Self {
+        OllamaProvider { settings }
+    }
+
+    fn request<'a>(&'a self, req: &'a LlmRequest, stream: bool) -> OllamaRequest<'a> {
+        OllamaRequest {
+            model: &self.settings.model,
+            prompt: &req.prompt,
+            stream,
+            format: req.json.then_some("json"),
+            options: OllamaOptions {
+                temperature: self.settings.temperature,
+                num_predict: self.settings.max_tokens,
+            },
+        }
+    }
+
+    fn url(&self) -> String {
+        format!(
+            "{}/api/generate",
+            self.settings.base_url.trim_end_matches('/')
+        )
+    }
+}
+
+/// Text of a non-streamed answer. Some models send escaped newlines in plain
+/// text; JSON answers keep theirs, since unescaping would break the strings.
+fn answer_text(response: &str, json: bool) -> String {
+    if json {
+        response.trim().to_string()
+    } else {
+        response.replace("\\n", "\n").trim().to_string()
+    }
+}
+
+impl LlmProvider for OllamaProvider {
+    fn generate<'a>(
+        &'a self,
+        client: &'a Client,
+        req: &'a LlmRequest,
+    ) -> BoxFuture<'a, Result<String, String>> {
+        Box::pin(async move {
+            let resp = client
+                .post(self.url())
+                .json(&self.request(req, false))
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            if !resp.status().is_success() {
+                return Err(format!("Ollama failed: {}", resp.status()));
+            }
+
+            let data: OllamaResponse = resp.json().await.map_err(|e| e.to_string())?;
+            Ok(answer_text(&data.response, req.json))
+        })
+    }
+
+    fn stream<'a>(
+        &'a self,
+        client: &'a Client,
+        req: &'a LlmRequest,
+        on_token: TokenSink<'a>,
+    ) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let resp = client
+                .post(self.url())
+                .json(&self.request(req, true))
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            if !resp.status().is_success() {
+                return Err(format!("Ollama failed: {}", resp.status()));
+            }
+
+            // Ollama streams one JSON object per line; a line can span chunks
+            let mut stream = resp.bytes_stream();
+            let mut buffer: Vec<u8> = Vec::new();
+            while let Some(item) = stream.next().await {
+                let chunk = item.map_err(|e| e.to_string())?;
+                buffer.extend_from_slice(&chunk);
+
+                while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                    let line: Vec<u8> = buffer.drain(..=pos).collect();
+                    let line = &line[..line.len() - 1];
+                    if line.is_empty() {
+                        continue;
+                    }
+                    match serde_json::from_slice::<OllamaResponse>(line) {
+                        Ok(ollama_response) => {
+                            on_token(ollama_response.response)?;
+                            if ollama_response.done {
+                                return Ok(());
+                            }
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to deserialize ollama response: {}", e);
+                        }
+                    }
+                }
+            }
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_answers_get_escaped_newlines_unescaped() {
+        assert_eq!(answer_text(" line one\\nline two\n", false), "line one\nline two");
+    }
+
+    #[test]
+    fn json_answers_keep_escaped_newlines() {
+        let response = r#"{"summary": "a\nb"}"#;
+        let text = answer_text(response, true);
+        assert_eq!(text, response);
+
+        let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(value["summary"], "a\nb");
+    }
+}
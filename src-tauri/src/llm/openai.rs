@@ -0,0 +1,143 @@
+use futures_util::future::BoxFuture;
+use futures_util::StreamExt;
+use reqwest::{Client, RequestBuilder};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use super::provider::{LlmProvider, LlmRequest, TokenSink};
+use super::settings::LlmSettings;
+
+/// Any server speaking the OpenAI chat completions API
+/// (llama.cpp server, vLLM, LM Studio, ...)
+pub struct OpenAiProvider {
+    settings: LlmSettings,
+}
+
+#[derive(Serialize)]
+struct ChatMessage<'a> {
+    role: &'a str,
+    content: &'a str,
+}
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<ChatMessage<'a>>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<Value>,
+}
+
+impl OpenAiProvider {
+    pub fn new(settings: LlmSettings) -> Self {
+        OpenAiProvider { settings }
+    }
+
+    /// `base_url` is expected to include the `/v1` prefix
+    fn post(&self, client: &Client, req: &LlmRequest, stream: bool) -> RequestBuilder {
+        let body = ChatRequest {
+            model: &self.settings.model,
+            messages: vec![ChatMessage {
+                role: "user",
+                content: &req.prompt,
+            }],
+            stream,
+            temperature: self.settings.temperature,
+            max_tokens: self.settings.max_tokens,
+            response_format: req.json.then(|| json!({ "type": "json_object" })),
+        };
+
+        let builder = client
+            .post(format!(
+                "{}/chat/completions",
+                self.settings.base_url.trim_end_matches('/')
+            ))
+            .json(&body);
+
+        match &self.settings.api_key {
+            Some(key) if !key.is_empty() => builder.bearer_auth(key),
+            _ => builder,
+        }
+    }
+}
+
+impl LlmProvider for OpenAiProvider {
+    fn generate<'a>(
+        &'a self,
+        client: &'a Client,
+        req: &'a LlmRequest,
+    ) -> BoxFuture<'a, Result<String, String>> {
+        Box::pin(async move {
+            let resp = self
+                .post(client, req, false)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            if !resp.status().is_success() {
+                return Err(format!("LLM server failed: {}", resp.status()));
+            }
+
+            let data: Value = resp.json().await.map_err(|e| e.to_string())?;
+            data["choices"][0]["message"]["content"]
+                .as_str()
+                .map(|s| s.trim().to_string())
+                .ok_or_else(|| "LLM server returned no content".to_string())
+        })
+    }
+
+    fn stream<'a>(
+        &'a self,
+        client: &'a Client,
+        req: &'a LlmRequest,
+        on_token: TokenSink<'a>,
+    ) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let resp = self
+                .post(client, req, true)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            if !resp.status().is_success() {
+                return Err(format!("LLM server failed: {}", resp.status()));
+            }
+
+            // Server-sent events: `data: {...}` lines, terminated by `data: [DONE]`
+            let mut stream = resp.bytes_stream();
+            let mut buffer: Vec<u8> = Vec::new();
+            while let Some(item) = stream.next().await {
+                let chunk = item.map_err(|e| e.to_string())?;
+                buffer.extend_from_slice(&chunk);
+
+                while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                    let line: Vec<u8> = buffer.drain(..=pos).collect();
+                    let line = String::from_utf8_lossy(&line);
+                    let Some(data) = line.trim().strip_prefix("data:") else {
+                        continue;
+                    };
+                    let data = data.trim();
+                    if data == "[DONE]" {
+                        return Ok(());
+                    }
+                    match serde_json::from_str::<Value>(data) {
+                        Ok(event) => {
+                            if let Some(token) = event["choices"][0]["delta"]["content"].as_str() {
+                                on_token(token.to_string())?;
+                            }
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to deserialize LLM stream event: {}", e);
+                        }
+                    }
+                }
+            }
+
+            Ok(())
+        })
+    }
+}
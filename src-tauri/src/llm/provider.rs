@@ -0,0 +1,77 @@
+use futures_util::future::BoxFuture;
+use reqwest::Client;
+
+use super::ollama::OllamaProvider;
+use super::openai::OpenAiProvider;
+use super::settings::{self, LlmSettings};
+use super::template::TemplateProvider;
+use crate::database;
+
+/// One prompt sent to a model
+pub struct LlmRequest {
+    /// Full prompt, instructions and data included
+    pub prompt: String,
+    /// The data part alone, used by providers that don't run a model
+    pub input: String,
+    /// Ask the backend to answer with a JSON object
+    pub json: bool,
+}
+
+impl LlmRequest {
+    pub fn new(prompt: String, input: &str) -> Self {
+        LlmRequest {
+            prompt,
+            input: input.to_string(),
+            json: false,
+        }
+    }
+}
+
+/// Callback receiving streamed tokens
+pub type TokenSink<'a> = &'a (dyn Fn(String) -> Result<(), String> + Send + Sync);
+
+pub trait LlmProvider: Send + Sync {
+    /// Whether answers can follow a JSON format (`LlmRequest::json`)
+    fn supports_json(&self) -> bool {
+        true
+    }
+
+    /// Generate the whole completion for `req`
+    fn generate<'a>(
+        &'a self,
+        client: &'a Client,
+        req: &'a LlmRequest,
+    ) -> BoxFuture<'a, Result<String, String>>;
+
+    /// Stream tokens to `on_token`; by default the full answer is sent in one piece
+    fn stream<'a>(
+        &'a self,
+        client: &'a Client,
+        req: &'a LlmRequest,
+        on_token: TokenSink<'a>,
+    ) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let text = self.generate(client, req).await?;
+            on_token(text)
+        })
+    }
+}
+
+/// Build the provider described by `settings`
+pub fn provider_from_settings(settings: LlmSettings) -> Result<Box<dyn LlmProvider>, String> {
+    match settings.provider.as_str() {
+        "ollama" => Ok(Box::new(OllamaProvider::new(settings))),
+        "openai" => Ok(Box::new(OpenAiProvider::new(settings))),
+        "template" => Ok(Box::new(TemplateProvider)),
+        other => Err(format!("Unknown LLM provider: {}", other)),
+    }
+}
+
+/// Provider configured in the settings table
+pub fn active_provider() -> Result<Box<dyn LlmProvider>, String> {
+    let settings = {
+        let conn = database::connection();
+        settings::load(&conn).map_err(|e| e.to_string())?
+    };
+    provider_from_settings(settings)
+}
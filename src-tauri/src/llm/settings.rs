@@ -0,0 +1,64 @@
+use rusqlite::{types::Type, Connection};
+use serde::{Deserialize, Serialize};
+
+use crate::database::settings;
+use crate::secrets;
+
+const LLM_SETTING: &str = "llm";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LlmSettings {
+    /// "ollama", "openai" (any OpenAI-compatible server) or "template" (no LLM)
+    pub provider: String,
+    pub model: String,
+    pub base_url: String,
+    /// Encrypted while stored, plain once loaded
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub temperature: Option<f64>,
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+}
+
+impl Default for LlmSettings {
+    fn default() -> Self {
+        LlmSettings {
+            provider: "ollama".into(),
+            model: "mistral".into(),
+            base_url: "http://localhost:11434".into(),
+            api_key: None,
+            temperature: None,
+            max_tokens: None,
+        }
+    }
+}
+
+/// Stored LLM settings, falling back to local Ollama + mistral
+pub fn load(conn: &Connection) -> rusqlite::Result<LlmSettings> {
+    let mut llm: LlmSettings = settings::get(conn, LLM_SETTING)?
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
+    llm.api_key = llm
+        .api_key
+        .map(|key| secrets::decrypt(&key))
+        .transpose()
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, Type::Text, e.into()))?;
+    Ok(llm)
+}
+
+pub fn save(conn: &Connection, llm: &LlmSettings) -> rusqlite::Result<()> {
+    let api_key = llm
+        .api_key
+        .as_deref()
+        .map(secrets::encrypt)
+        .transpose()
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+    let stored = LlmSettings {
+        api_key,
+        ..llm.clone()
+    };
+    let json = serde_json::to_string(&stored)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    settings::set(conn, LLM_SETTING, &json)
+}
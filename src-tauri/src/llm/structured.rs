@@ -0,0 +1,73 @@
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+
+use super::provider::{LlmProvider, LlmRequest};
+
+/// Answers tried before giving up: the first one plus repairs
+const MAX_ATTEMPTS: usize = 3;
+/// Start of the error returned when no answer could be parsed
+const INVALID_JSON: &str = "No valid JSON";
+
+/// The JSON object in `text`, without code fences or chatter around it
+fn json_object(text: &str) -> &str {
+    match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => text.trim(),
+    }
+}
+
+/// Parse `text` as `T` and run `validate` on it
+fn parse<T, V>(text: &str, validate: &V) -> Result<T, String>
+where
+    T: DeserializeOwned,
+    V: Fn(&T) -> Result<(), String>,
+{
+    let value: T = serde_json::from_str(json_object(text)).map_err(|e| e.to_string())?;
+    validate(&value)?;
+    Ok(value)
+}
+
+/// Ask for a JSON answer and parse it as `T`. Malformed or invalid answers
+/// are sent back to the model together with the error, up to `MAX_ATTEMPTS`
+/// answers in total. Transport errors are returned right away.
+pub async fn generate_json<T, V>(
+    client: &Client,
+    provider: &dyn LlmProvider,
+    prompt: String,
+    input: &str,
+    validate: V,
+) -> Result<T, String>
+where
+    T: DeserializeOwned,
+    V: Fn(&T) -> Result<(), String>,
+{
+    let mut request = LlmRequest::new(prompt.clone(), input);
+    request.json = true;
+
+    let mut last_error = String::new();
+    for attempt in 1..=MAX_ATTEMPTS {
+        let answer = provider.generate(client, &request).await?;
+        match parse(&answer, &validate) {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                eprintln!("⚠️ Invalid JSON answer (attempt {}): {}", attempt, e);
+                request.prompt = format!(
+                    "{}\n\nYour previous answer was rejected: {}\n\nPrevious answer:\n{}\n\nReply again with only the corrected JSON object.",
+                    prompt, e, answer
+                );
+                last_error = e;
+            }
+        }
+    }
+
+    Err(format!(
+        "{} after {} attempts: {}",
+        INVALID_JSON, MAX_ATTEMPTS, last_error
+    ))
+}
+
+/// Whether `generate_json` failed because the model kept answering with
+/// invalid JSON, as opposed to not answering at all
+pub fn is_invalid_json(err: &str) -> bool {
+    err.starts_with(INVALID_JSON)
+}
@@ -0,0 +1,45 @@
+use futures_util::future::BoxFuture;
+use reqwest::Client;
+
+use super::provider::{LlmProvider, LlmRequest};
+
+/// Offline provider that never calls a model: it turns the first lines
+/// of the input into bullet points, so the same input always gives the same text
+pub struct TemplateProvider;
+
+const MAX_BULLETS: usize = 5;
+const MAX_BULLET_CHARS: usize = 80;
+
+impl LlmProvider for TemplateProvider {
+    fn supports_json(&self) -> bool {
+        false
+    }
+
+    fn generate<'a>(
+        &'a self,
+        _client: &'a Client,
+        req: &'a LlmRequest,
+    ) -> BoxFuture<'a, Result<String, String>> {
+        Box::pin(async move {
+            let bullets: Vec<String> = req
+                .input
+                .lines()
+                .map(|line| line.trim().trim_start_matches('•').trim())
+                .filter(|line| !line.is_empty())
+                .take(MAX_BULLETS)
+                .map(|line| {
+                    let mut bullet: String = line.chars().take(MAX_BULLET_CHARS).collect();
+                    if line.chars().count() > MAX_BULLET_CHARS {
+                        bullet.push('…');
+                    }
+                    format!("• {}", bullet)
+                })
+                .collect();
+
+            if bullets.is_empty() {
+                return Ok("No activity recorded.".to_string());
+            }
+            Ok(bullets.join("\n"))
+        })
+    }
+}
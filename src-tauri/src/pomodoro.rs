@@ -0,0 +1,498 @@
+use chrono::{DateTime, Duration, Utc};
+use once_cell::sync::Lazy;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+
+use crate::database::{self, settings};
+use crate::users::active_user_id;
+
+const CONFIG_SETTING: &str = "pomodoro_config";
+/// The running timer, so it survives a restart
+const STATE_SETTING: &str = "pomodoro_state";
+/// Title of the `events` row created when a pomodoro isn't started for one
+const DEFAULT_TITLE: &str = "Focus";
+
+/// Emitted every second while a timer exists, with a `PomodoroStatus`
+const TICK_EVENT: &str = "pomodoro-tick";
+/// Emitted when a phase ends, with the `PomodoroStatus` of the new phase
+const PHASE_EVENT: &str = "pomodoro-phase";
+const TICK_INTERVAL_SECS: u64 = 1;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PomodoroConfig {
+    pub work_minutes: u32,
+    pub short_break_minutes: u32,
+    pub long_break_minutes: u32,
+    /// Every n-th work interval is followed by a long break
+    pub long_break_every: u32,
+}
+
+impl Default for PomodoroConfig {
+    fn default() -> Self {
+        PomodoroConfig {
+            work_minutes: 25,
+            short_break_minutes: 5,
+            long_break_minutes: 15,
+            long_break_every: 4,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Work,
+    ShortBreak,
+    LongBreak,
+}
+
+/// The timer as persisted. Time left is derived from timestamps rather than
+/// counted down, so ticks missed while the app was closed don't matter.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct TimerState {
+    phase: Phase,
+    /// `events` row the pomodoros belong to
+    event_id: i64,
+    /// `pomodoro_sessions` row of the current work interval; none during breaks
+    /// and while the next work interval waits to be resumed
+    session_id: Option<i64>,
+    /// 1-based number of the current (or, during a break, the last) work interval
+    interval_number: i64,
+    phase_seconds: i64,
+    phase_started: DateTime<Utc>,
+    /// Seconds paused so far in this phase, the running pause excluded
+    paused_seconds: i64,
+    paused_at: Option<DateTime<Utc>>,
+}
+
+impl TimerState {
+    fn paused_total(&self, now: DateTime<Utc>) -> i64 {
+        self.paused_seconds
+            + self
+                .paused_at
+                .map(|at| (now - at).num_seconds())
+                .unwrap_or(0)
+    }
+
+    fn remaining(&self, now: DateTime<Utc>) -> i64 {
+        let elapsed = (now - self.phase_started).num_seconds() - self.paused_total(now);
+        (self.phase_seconds - elapsed).max(0)
+    }
+
+    /// When a running phase runs out
+    fn phase_end(&self) -> DateTime<Utc> {
+        self.phase_started + Duration::seconds(self.phase_seconds + self.paused_seconds)
+    }
+
+    fn status(&self, now: DateTime<Utc>) -> PomodoroStatus {
+        PomodoroStatus {
+            phase: self.phase,
+            event_id: self.event_id,
+            session_id: self.session_id,
+            interval_number: self.interval_number,
+            phase_seconds: self.phase_seconds,
+            remaining_seconds: self.remaining(now),
+            paused: self.paused_at.is_some(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct PomodoroStatus {
+    pub phase: Phase,
+    pub event_id: i64,
+    pub session_id: Option<i64>,
+    pub interval_number: i64,
+    pub phase_seconds: i64,
+    pub remaining_seconds: i64,
+    pub paused: bool,
+}
+
+/// The live timer. Lock it before the database, never the other way round.
+static TIMER: Lazy<Mutex<Option<TimerState>>> = Lazy::new(|| {
+    let conn = database::connection();
+    let state = settings::get(&conn, STATE_SETTING)
+        .ok()
+        .flatten()
+        .and_then(|json| serde_json::from_str(&json).ok());
+    Mutex::new(state)
+});
+
+fn load_config(conn: &Connection) -> rusqlite::Result<PomodoroConfig> {
+    Ok(settings::get(conn, CONFIG_SETTING)?
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default())
+}
+
+fn save_state(conn: &Connection, state: Option<&TimerState>) -> rusqlite::Result<()> {
+    match state {
+        Some(state) => {
+            let json = serde_json::to_string(state)
+                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+            settings::set(conn, STATE_SETTING, &json)
+        }
+        None => settings::delete(conn, STATE_SETTING),
+    }
+}
+
+fn event_exists(conn: &Connection, event_id: i64) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT 1 FROM events WHERE id = ?1 AND user_id = ?2",
+        params![event_id, active_user_id(conn)],
+        |_| Ok(()),
+    )
+    .optional()
+    .map(|row| row.is_some())
+}
+
+/// `events` row for pomodoros started without one, planned for a single interval
+fn create_event(
+    conn: &Connection,
+    title: &str,
+    start: DateTime<Utc>,
+    config: &PomodoroConfig,
+) -> rusqlite::Result<i64> {
+    let now = Utc::now().to_rfc3339();
+    let end = start + Duration::minutes(config.work_minutes as i64);
+    conn.execute(
+        "INSERT INTO events (
+            user_id, title, category, planned_start, planned_end, actual_start,
+            status, created_at, updated_at
+        ) VALUES (?1, ?2, 'pomodoro', ?3, ?4, ?3, 'in_progress', ?5, ?5)",
+        params![
+            active_user_id(conn),
+            title,
+            start.to_rfc3339(),
+            end.to_rfc3339(),
+            now
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+fn start_session(
+    conn: &Connection,
+    event_id: i64,
+    interval_number: i64,
+    start: DateTime<Utc>,
+) -> rusqlite::Result<i64> {
+    let now = Utc::now().to_rfc3339();
+    conn.execute(
+        "INSERT INTO pomodoro_sessions (
+            event_id, start_time, interval_number, created_at, updated_at
+        ) VALUES (?1, ?2, ?3, ?4, ?4)",
+        params![event_id, start.to_rfc3339(), interval_number, now],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Close the session of a work phase ending at `at`
+fn end_session(
+    conn: &Connection,
+    state: &TimerState,
+    at: DateTime<Utc>,
+    completed: bool,
+) -> rusqlite::Result<()> {
+    let Some(session_id) = state.session_id else {
+        return Ok(());
+    };
+    conn.execute(
+        "UPDATE pomodoro_sessions
+         SET end_time = ?2, paused_duration = ?3, completed = ?4, updated_at = ?5
+         WHERE id = ?1",
+        params![
+            session_id,
+            at.to_rfc3339(),
+            state.paused_total(at),
+            completed as i64,
+            Utc::now().to_rfc3339()
+        ],
+    )?;
+    Ok(())
+}
+
+fn work_phase(
+    conn: &Connection,
+    config: &PomodoroConfig,
+    event_id: i64,
+    interval_number: i64,
+    at: DateTime<Utc>,
+) -> rusqlite::Result<TimerState> {
+    Ok(TimerState {
+        phase: Phase::Work,
+        event_id,
+        session_id: Some(start_session(conn, event_id, interval_number, at)?),
+        interval_number,
+        phase_seconds: config.work_minutes as i64 * 60,
+        phase_started: at,
+        paused_seconds: 0,
+        paused_at: None,
+    })
+}
+
+/// End the current phase at `at` and return the next one. A work phase is
+/// followed by a break; a break by the next work interval, which waits paused
+/// unless the break was skipped. A waiting interval gets its session once it is
+/// resumed, so time spent away isn't recorded as work.
+fn next_phase(
+    conn: &Connection,
+    config: &PomodoroConfig,
+    state: &TimerState,
+    at: DateTime<Utc>,
+    completed: bool,
+) -> rusqlite::Result<TimerState> {
+    match state.phase {
+        Phase::Work => {
+            end_session(conn, state, at, completed)?;
+            let long = state.interval_number % config.long_break_every.max(1) as i64 == 0;
+            let (phase, minutes) = if long {
+                (Phase::LongBreak, config.long_break_minutes)
+            } else {
+                (Phase::ShortBreak, config.short_break_minutes)
+            };
+            Ok(TimerState {
+                phase,
+                session_id: None,
+                phase_seconds: minutes as i64 * 60,
+                phase_started: at,
+                paused_seconds: 0,
+                paused_at: None,
+                ..state.clone()
+            })
+        }
+        Phase::ShortBreak | Phase::LongBreak if completed => Ok(TimerState {
+            phase: Phase::Work,
+            session_id: None,
+            interval_number: state.interval_number + 1,
+            phase_seconds: config.work_minutes as i64 * 60,
+            phase_started: at,
+            paused_seconds: 0,
+            paused_at: Some(at),
+            ..state.clone()
+        }),
+        Phase::ShortBreak | Phase::LongBreak => {
+            work_phase(conn, config, state.event_id, state.interval_number + 1, at)
+        }
+    }
+}
+
+/// Record the end of the pomodoros on their `events` row
+fn finish_event(conn: &Connection, event_id: i64, at: DateTime<Utc>) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE events SET actual_end = ?2, status = 'completed', updated_at = ?3 WHERE id = ?1",
+        params![event_id, at.to_rfc3339(), Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+/// Run `f` on the live timer and persist the result
+fn with_timer<T>(
+    f: impl FnOnce(&Connection, &PomodoroConfig, &mut Option<TimerState>) -> Result<T, String>,
+) -> Result<T, String> {
+    let mut timer = TIMER.lock().map_err(|e| e.to_string())?;
+    let conn = database::connection();
+    let config = load_config(&conn).map_err(|e| e.to_string())?;
+    let result = f(&conn, &config, &mut timer)?;
+    save_state(&conn, timer.as_ref()).map_err(|e| e.to_string())?;
+    Ok(result)
+}
+
+fn running(timer: &mut Option<TimerState>) -> Result<&mut TimerState, String> {
+    timer
+        .as_mut()
+        .ok_or_else(|| "No pomodoro is running".to_string())
+}
+
+fn emit_status(app: &AppHandle, event: &str, status: &PomodoroStatus) {
+    if let Err(e) = app.emit(event, status) {
+        eprintln!("⚠️ Could not emit {}: {}", event, e);
+    }
+}
+
+/// `events` row and session of the work interval running right now, if any
+pub fn active_work() -> Option<(i64, i64)> {
+    let timer = TIMER.lock().ok()?;
+    let state = timer.as_ref()?;
+    if state.phase != Phase::Work || state.paused_at.is_some() {
+        return None;
+    }
+    Some((state.event_id, state.session_id?))
+}
+
+/// Count one more distraction in a work interval
+pub fn add_distraction(conn: &Connection, session_id: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE pomodoro_sessions
+         SET distraction_count = distraction_count + 1, updated_at = ?2
+         WHERE id = ?1",
+        params![session_id, Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+/// Move past every phase that ran out, then report the timer. Phases that
+/// ended while the app was closed end at the time they ran out.
+fn tick() -> Result<(Option<PomodoroStatus>, Vec<PomodoroStatus>), String> {
+    let mut timer = TIMER.lock().map_err(|e| e.to_string())?;
+    let now = Utc::now();
+    let ran_out = |timer: &Option<TimerState>| {
+        timer
+            .as_ref()
+            .is_some_and(|t| t.paused_at.is_none() && t.remaining(now) == 0)
+    };
+
+    let mut changes = Vec::new();
+    if ran_out(&timer) {
+        let conn = database::connection();
+        let config = load_config(&conn).map_err(|e| e.to_string())?;
+        while let Some(state) = timer.as_ref().filter(|_| ran_out(&timer)) {
+            let next = next_phase(&conn, &config, state, state.phase_end(), true)
+                .map_err(|e| e.to_string())?;
+            changes.push(next.status(now));
+            *timer = Some(next);
+        }
+        save_state(&conn, timer.as_ref()).map_err(|e| e.to_string())?;
+    }
+    Ok((timer.as_ref().map(|t| t.status(now)), changes))
+}
+
+/// Background loop emitting the timer every second
+pub async fn run(app: AppHandle) {
+    loop {
+        match tick() {
+            Ok((status, changes)) => {
+                for change in &changes {
+                    emit_status(&app, PHASE_EVENT, change);
+                }
+                if let Some(status) = status {
+                    emit_status(&app, TICK_EVENT, &status);
+                }
+            }
+            Err(e) => eprintln!("Pomodoro tick failed: {}", e),
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(TICK_INTERVAL_SECS)).await;
+    }
+}
+
+#[tauri::command]
+pub fn get_pomodoro_config() -> Result<PomodoroConfig, String> {
+    let conn = database::connection();
+    load_config(&conn).map_err(|e| e.to_string())
+}
+
+/// Lengths apply from the next phase on
+#[tauri::command]
+pub fn set_pomodoro_config(config: PomodoroConfig) -> Result<(), String> {
+    if config.work_minutes == 0
+        || config.short_break_minutes == 0
+        || config.long_break_minutes == 0
+        || config.long_break_every == 0
+    {
+        return Err("Pomodoro lengths and the long break interval must be at least 1".into());
+    }
+    let json = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+    let conn = database::connection();
+    settings::set(&conn, CONFIG_SETTING, &json).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_pomodoro_status() -> Result<Option<PomodoroStatus>, String> {
+    let timer = TIMER.lock().map_err(|e| e.to_string())?;
+    Ok(timer.as_ref().map(|t| t.status(Utc::now())))
+}
+
+/// Start a work interval for `event_id` (an `events` row), or for a new
+/// event titled `title`
+#[tauri::command]
+pub fn start_pomodoro(
+    app_handle: AppHandle,
+    title: Option<String>,
+    event_id: Option<i64>,
+) -> Result<PomodoroStatus, String> {
+    let status = with_timer(|conn, config, timer| {
+        if timer.is_some() {
+            return Err("A pomodoro is already running".into());
+        }
+        let now = Utc::now();
+        let event_id = match event_id {
+            Some(id) => {
+                if !event_exists(conn, id).map_err(|e| e.to_string())? {
+                    return Err(format!("Event {} not found", id));
+                }
+                id
+            }
+            None => create_event(conn, title.as_deref().unwrap_or(DEFAULT_TITLE), now, config)
+                .map_err(|e| e.to_string())?,
+        };
+        let state = work_phase(conn, config, event_id, 1, now).map_err(|e| e.to_string())?;
+        let status = state.status(now);
+        *timer = Some(state);
+        Ok(status)
+    })?;
+    emit_status(&app_handle, PHASE_EVENT, &status);
+    Ok(status)
+}
+
+#[tauri::command]
+pub fn pause_pomodoro(app_handle: AppHandle) -> Result<PomodoroStatus, String> {
+    let status = with_timer(|_, _, timer| {
+        let state = running(timer)?;
+        let now = Utc::now();
+        if state.paused_at.is_none() {
+            state.paused_at = Some(now);
+        }
+        Ok(state.status(now))
+    })?;
+    emit_status(&app_handle, TICK_EVENT, &status);
+    Ok(status)
+}
+
+#[tauri::command]
+pub fn resume_pomodoro(app_handle: AppHandle) -> Result<PomodoroStatus, String> {
+    let status = with_timer(|conn, config, timer| {
+        let state = running(timer)?;
+        let now = Utc::now();
+        if state.phase == Phase::Work && state.session_id.is_none() {
+            // The interval queued after a break starts only now
+            *state = work_phase(conn, config, state.event_id, state.interval_number, now)
+                .map_err(|e| e.to_string())?;
+        } else if let Some(at) = state.paused_at.take() {
+            state.paused_seconds += (now - at).num_seconds();
+        }
+        Ok(state.status(now))
+    })?;
+    emit_status(&app_handle, TICK_EVENT, &status);
+    Ok(status)
+}
+
+/// End the current phase now. A skipped work interval is stored as not completed.
+#[tauri::command]
+pub fn skip_pomodoro(app_handle: AppHandle) -> Result<PomodoroStatus, String> {
+    let status = with_timer(|conn, config, timer| {
+        let state = running(timer)?;
+        let now = Utc::now();
+        let next = next_phase(conn, config, state, now, false).map_err(|e| e.to_string())?;
+        let status = next.status(now);
+        *timer = Some(next);
+        Ok(status)
+    })?;
+    emit_status(&app_handle, PHASE_EVENT, &status);
+    Ok(status)
+}
+
+/// Stop the timer. An unfinished work interval is stored as not completed.
+#[tauri::command]
+pub fn stop_pomodoro() -> Result<(), String> {
+    with_timer(|conn, _, timer| {
+        let Some(state) = timer.take() else {
+            return Ok(());
+        };
+        let now = Utc::now();
+        if state.phase == Phase::Work {
+            end_session(conn, &state, now, false).map_err(|e| e.to_string())?;
+        }
+        finish_event(conn, state.event_id, now).map_err(|e| e.to_string())
+    })
+}
@@ -0,0 +1,467 @@
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use reqwest::Client;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::activity::{
+    calendars::load_plan_events,
+    categorize::Productivity,
+    store::{self, ActivityBlock},
+};
+use crate::database;
+use crate::llm::{active_provider, LlmRequest};
+use crate::timezone::{day_range, parse_date, today, user_timezone};
+use crate::users::active_user_id;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportKind {
+    /// Monday to Sunday
+    Weekly,
+    /// A calendar month
+    Monthly,
+}
+
+impl ReportKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ReportKind::Weekly => "weekly",
+            ReportKind::Monthly => "monthly",
+        }
+    }
+
+    fn parse(value: &str) -> rusqlite::Result<ReportKind> {
+        match value {
+            "weekly" => Ok(ReportKind::Weekly),
+            "monthly" => Ok(ReportKind::Monthly),
+            other => Err(rusqlite::Error::InvalidColumnType(
+                1,
+                format!("report kind '{}'", other),
+                rusqlite::types::Type::Text,
+            )),
+        }
+    }
+
+    /// First and last day of the period containing `date`
+    fn period(&self, date: NaiveDate) -> (NaiveDate, NaiveDate) {
+        match self {
+            ReportKind::Weekly => {
+                let first = date - Duration::days(date.weekday().num_days_from_monday() as i64);
+                (first, first + Duration::days(6))
+            }
+            ReportKind::Monthly => {
+                let first = date.with_day(1).unwrap();
+                let next = if first.month() == 12 {
+                    NaiveDate::from_ymd_opt(first.year() + 1, 1, 1)
+                } else {
+                    NaiveDate::from_ymd_opt(first.year(), first.month() + 1, 1)
+                }
+                .unwrap();
+                (first, next - Duration::days(1))
+            }
+        }
+    }
+}
+
+/// Time in one category, with the change against the previous period
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CategoryTrend {
+    pub category: String,
+    pub productivity: Productivity,
+    pub hours: f64,
+    pub previous_hours: f64,
+    pub delta_hours: f64,
+}
+
+/// Change of the headline numbers against the previous period
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TrendDeltas {
+    pub active_hours: f64,
+    pub planned_hours: Option<f64>,
+    pub focus_score: f64,
+    pub distracted_blocks: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ReportMetrics {
+    pub active_hours: f64,
+    pub afk_hours: f64,
+    /// Hours of timed calendar plans; `None` when the calendar could not be read
+    pub planned_hours: Option<f64>,
+    /// Actual minus planned hours
+    pub plan_variance_hours: Option<f64>,
+    pub productive_hours: f64,
+    pub distracting_hours: f64,
+    /// Productive share of productive + distracting time, 0-100
+    pub focus_score: f64,
+    /// Stored blocks that contain distracting time
+    pub distracted_blocks: i64,
+    /// Rows in the `distractions` table
+    pub distraction_events: i64,
+    pub blocks_recorded: i64,
+    pub categories: Vec<CategoryTrend>,
+    pub trend: TrendDeltas,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Report {
+    pub id: i64,
+    pub kind: ReportKind,
+    /// First day, `YYYY-MM-DD`
+    pub period_start: String,
+    /// Last day (inclusive), `YYYY-MM-DD`
+    pub period_end: String,
+    pub metrics: ReportMetrics,
+    pub narrative: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+fn hours(seconds: f64) -> f64 {
+    seconds / 3600.0
+}
+
+/// Hours of timed plans overlapping the period
+async fn planned_hours(client: &Client, tz: &Tz, first: NaiveDate, last: NaiveDate) -> Option<f64> {
+    let (start, _) = day_range(tz, first);
+    let (_, end) = day_range(tz, last);
+    match load_plan_events(client, start, end).await {
+        Ok(events) => Some(
+            events
+                .iter()
+                .filter(|ev| !ev.is_all_day())
+                .filter_map(|ev| ev.time_range(tz))
+                .map(|(s, e)| hours((e.min(end) - s.max(start)).num_seconds().max(0) as f64))
+                .sum(),
+        ),
+        Err(e) => {
+            eprintln!("⚠️ Report without planned time: {}", e);
+            None
+        }
+    }
+}
+
+/// Totals of one period, before trends are known
+fn aggregate(
+    blocks: &[ActivityBlock],
+    planned_hours: Option<f64>,
+    distraction_events: i64,
+) -> ReportMetrics {
+    let mut metrics = ReportMetrics {
+        planned_hours,
+        distraction_events,
+        blocks_recorded: blocks.len() as i64,
+        ..Default::default()
+    };
+
+    for block in blocks {
+        metrics.active_hours += hours(block.active_seconds);
+        metrics.afk_hours += hours(block.afk_seconds);
+        let mut distracted = false;
+        for c in &block.categories {
+            match c.productivity {
+                Productivity::Productive => metrics.productive_hours += hours(c.seconds),
+                Productivity::Distracting => {
+                    metrics.distracting_hours += hours(c.seconds);
+                    distracted = true;
+                }
+                Productivity::Neutral => {}
+            }
+        }
+        if distracted {
+            metrics.distracted_blocks += 1;
+        }
+    }
+
+    let focused = metrics.productive_hours + metrics.distracting_hours;
+    if focused > 0.0 {
+        metrics.focus_score = metrics.productive_hours / focused * 100.0;
+    }
+    metrics.plan_variance_hours = planned_hours.map(|planned| metrics.active_hours - planned);
+    metrics
+}
+
+/// Hours per category
+fn category_hours(blocks: &[ActivityBlock]) -> BTreeMap<(String, Productivity), f64> {
+    let mut totals = BTreeMap::new();
+    for c in blocks.iter().flat_map(|b| &b.categories) {
+        *totals
+            .entry((c.category.clone(), c.productivity))
+            .or_default() += hours(c.seconds);
+    }
+    totals
+}
+
+fn category_trends(current: &[ActivityBlock], previous: &[ActivityBlock]) -> Vec<CategoryTrend> {
+    let now = category_hours(current);
+    let before = category_hours(previous);
+
+    let mut keys: Vec<&(String, Productivity)> = now.keys().chain(before.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    let mut trends: Vec<CategoryTrend> = keys
+        .into_iter()
+        .map(|key| {
+            let hours = now.get(key).copied().unwrap_or_default();
+            let previous_hours = before.get(key).copied().unwrap_or_default();
+            CategoryTrend {
+                category: key.0.clone(),
+                productivity: key.1,
+                hours,
+                previous_hours,
+                delta_hours: hours - previous_hours,
+            }
+        })
+        .collect();
+    trends.sort_by(|a, b| b.hours.total_cmp(&a.hours));
+    trends
+}
+
+fn distraction_count(
+    conn: &Connection,
+    tz: &Tz,
+    first: NaiveDate,
+    last: NaiveDate,
+) -> rusqlite::Result<i64> {
+    let (start, _) = day_range(tz, first);
+    let (_, end) = day_range(tz, last);
+    conn.query_row(
+        "SELECT COUNT(*) FROM distractions
+         WHERE user_id = ?1 AND start_time >= ?2 AND start_time < ?3",
+        params![active_user_id(conn), start.to_rfc3339(), end.to_rfc3339()],
+        |row| row.get(0),
+    )
+}
+
+/// Stored blocks and distraction count of a period
+fn load_period(
+    tz: &Tz,
+    first: NaiveDate,
+    last: NaiveDate,
+) -> Result<(Vec<ActivityBlock>, i64), String> {
+    let (start, _) = day_range(tz, first);
+    let (_, end) = day_range(tz, last);
+    let conn = database::connection();
+    let blocks = store::blocks_between(&conn, start, end).map_err(|e| e.to_string())?;
+    let distractions = distraction_count(&conn, tz, first, last).map_err(|e| e.to_string())?;
+    Ok((blocks, distractions))
+}
+
+/// Metrics of the period `[first, last]` with trends against `previous`
+async fn build_metrics(
+    client: &Client,
+    tz: &Tz,
+    (first, last): (NaiveDate, NaiveDate),
+    (prev_first, prev_last): (NaiveDate, NaiveDate),
+) -> Result<ReportMetrics, String> {
+    let (blocks, distractions) = load_period(tz, first, last)?;
+    let (prev_blocks, prev_distractions) = load_period(tz, prev_first, prev_last)?;
+
+    let planned = planned_hours(client, tz, first, last).await;
+    let prev_planned = planned_hours(client, tz, prev_first, prev_last).await;
+
+    let mut metrics = aggregate(&blocks, planned, distractions);
+    let previous = aggregate(&prev_blocks, prev_planned, prev_distractions);
+
+    metrics.categories = category_trends(&blocks, &prev_blocks);
+    metrics.trend = TrendDeltas {
+        active_hours: metrics.active_hours - previous.active_hours,
+        planned_hours: planned.zip(prev_planned).map(|(now, before)| now - before),
+        focus_score: metrics.focus_score - previous.focus_score,
+        distracted_blocks: metrics.distracted_blocks - previous.distracted_blocks,
+    };
+    Ok(metrics)
+}
+
+/// Numbers of the report as plain text for the model
+fn metrics_text(metrics: &ReportMetrics) -> String {
+    let mut lines = vec![
+        format!(
+            "Active: {:.1}h ({:+.1}h vs previous period)",
+            metrics.active_hours, metrics.trend.active_hours
+        ),
+        format!("AFK: {:.1}h", metrics.afk_hours),
+        format!(
+            "Focus score: {:.0} ({:+.0})",
+            metrics.focus_score, metrics.trend.focus_score
+        ),
+        format!(
+            "Blocks with distractions: {} ({:+})",
+            metrics.distracted_blocks, metrics.trend.distracted_blocks
+        ),
+    ];
+    if let (Some(planned), Some(variance)) = (metrics.planned_hours, metrics.plan_variance_hours) {
+        lines.push(format!(
+            "Planned: {:.1}h, actual minus planned: {:+.1}h",
+            planned, variance
+        ));
+    }
+    lines.push("Categories:".to_string());
+    for c in &metrics.categories {
+        lines.push(format!(
+            "- {} ({}): {:.1}h ({:+.1}h)",
+            c.category,
+            c.productivity.as_str(),
+            c.hours,
+            c.delta_hours
+        ));
+    }
+    lines.join("\n")
+}
+
+async fn write_narrative(
+    client: &Client,
+    kind: ReportKind,
+    metrics: &ReportMetrics,
+) -> Result<String, String> {
+    let provider = active_provider()?;
+    let data = metrics_text(metrics);
+    let request = LlmRequest::new(
+        format!(
+            "You are a brutally honest productivity coach.\n\nHere is my {} report:\n{}\n\nWrite a short narrative: what went well, what slipped compared to the previous period, and 3 concrete changes for the next one.",
+            kind.as_str(),
+            data
+        ),
+        &data,
+    );
+    provider.generate(client, &request).await
+}
+
+fn report_from_row(row: &Row) -> rusqlite::Result<Report> {
+    let metrics: String = row.get(4)?;
+    Ok(Report {
+        id: row.get(0)?,
+        kind: ReportKind::parse(&row.get::<_, String>(1)?)?,
+        period_start: row.get(2)?,
+        period_end: row.get(3)?,
+        metrics: serde_json::from_str(&metrics).unwrap_or_default(),
+        narrative: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+const REPORT_COLUMNS: &str =
+    "id, kind, period_start, period_end, metrics, narrative, created_at, updated_at";
+
+fn find_report(
+    conn: &Connection,
+    kind: ReportKind,
+    period_start: NaiveDate,
+) -> rusqlite::Result<Option<Report>> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM reports WHERE user_id = ?1 AND kind = ?2 AND period_start = ?3",
+            REPORT_COLUMNS
+        ),
+        params![
+            active_user_id(conn),
+            kind.as_str(),
+            period_start.format("%Y-%m-%d").to_string()
+        ],
+        report_from_row,
+    )
+    .optional()
+}
+
+fn save_report(
+    conn: &Connection,
+    kind: ReportKind,
+    (first, last): (NaiveDate, NaiveDate),
+    metrics: &ReportMetrics,
+    narrative: Option<&str>,
+) -> rusqlite::Result<Report> {
+    let now = Utc::now().to_rfc3339();
+    let metrics = serde_json::to_string(metrics)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    conn.execute(
+        "INSERT INTO reports (
+            user_id, kind, period_start, period_end, metrics, narrative, created_at, updated_at
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)
+        ON CONFLICT(user_id, kind, period_start) DO UPDATE SET
+            period_end = excluded.period_end,
+            metrics = excluded.metrics,
+            narrative = excluded.narrative,
+            updated_at = excluded.updated_at",
+        params![
+            active_user_id(conn),
+            kind.as_str(),
+            first.format("%Y-%m-%d").to_string(),
+            last.format("%Y-%m-%d").to_string(),
+            metrics,
+            narrative,
+            now
+        ],
+    )?;
+    find_report(conn, kind, first)?.ok_or(rusqlite::Error::QueryReturnedNoRows)
+}
+
+/// Build (or rebuild) the report of the period containing `date`
+/// (`YYYY-MM-DD`, today by default) and store it
+#[tauri::command]
+pub async fn generate_report(kind: ReportKind, date: Option<String>) -> Result<Report, String> {
+    let client = Client::new();
+    let tz = user_timezone();
+    let date = match date {
+        Some(d) => parse_date(&d)?,
+        None => today(&tz),
+    };
+    let period = kind.period(date);
+    let previous = kind.period(period.0 - Duration::days(1));
+
+    let metrics = build_metrics(&client, &tz, period, previous).await?;
+    // The numbers are worth keeping even when no model is available
+    let narrative = match write_narrative(&client, kind, &metrics).await {
+        Ok(text) => Some(text),
+        Err(e) => {
+            eprintln!("⚠️ Report narrative failed: {}", e);
+            None
+        }
+    };
+
+    let conn = database::connection();
+    save_report(&conn, kind, period, &metrics, narrative.as_deref()).map_err(|e| e.to_string())
+}
+
+/// Stored report of the period containing `date`, if one was generated
+#[tauri::command]
+pub fn get_report(kind: ReportKind, date: Option<String>) -> Result<Option<Report>, String> {
+    let tz = user_timezone();
+    let date = match date {
+        Some(d) => parse_date(&d)?,
+        None => today(&tz),
+    };
+    let conn = database::connection();
+    find_report(&conn, kind, kind.period(date).0).map_err(|e| e.to_string())
+}
+
+/// Stored reports, newest period first
+#[tauri::command]
+pub fn list_reports(kind: Option<ReportKind>, limit: Option<u32>) -> Result<Vec<Report>, String> {
+    let conn = database::connection();
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM reports
+             WHERE user_id = ?1 AND (?2 IS NULL OR kind = ?2)
+             ORDER BY period_start DESC, kind
+             LIMIT ?3",
+            REPORT_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map(
+            params![
+                active_user_id(&conn),
+                kind.map(|k| k.as_str()),
+                limit.unwrap_or(52)
+            ],
+            report_from_row,
+        )
+        .map_err(|e| e.to_string())?;
+    rows.collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,261 @@
+use aes_gcm::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use once_cell::sync::Lazy;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use crate::database::{anthyre_dir, settings};
+
+/// Marks values written by `encrypt`; anything else is legacy plaintext
+const PREFIX: &str = "enc:v1:";
+/// When set, the key is derived from this passphrase instead of the key file
+const PASSPHRASE_ENV: &str = "ANTHYRE_PASSPHRASE";
+const KEY_FILE: &str = "secret.key";
+/// Salt for the passphrase; not secret, but must stay the same
+const SALT_FILE: &str = "secret.salt";
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+/// A known value encrypted with the key, to notice at startup that the key changed
+const KEY_CHECK_SETTING: &str = "secret_key_check";
+const KEY_CHECK_PLAIN: &str = "anthyre";
+
+static KEY: Lazy<Result<Key<Aes256Gcm>, String>> = Lazy::new(load_key);
+
+/// Read `name` from the data dir, creating it with `len` random bytes
+/// (readable by the owner only) on first use
+fn read_or_create(name: &str, len: usize) -> Result<Vec<u8>, String> {
+    let path = anthyre_dir().join(name);
+    if path.exists() {
+        restrict_permissions(&path)?;
+        let bytes = fs::read(&path).map_err(|e| format!("Could not read {}: {}", name, e))?;
+        if bytes.len() != len {
+            return Err(format!("{} is corrupt, expected {} bytes", name, len));
+        }
+        return Ok(bytes);
+    }
+
+    let mut bytes = vec![0u8; len];
+    OsRng.fill_bytes(&mut bytes);
+    fs::create_dir_all(anthyre_dir()).map_err(|e| e.to_string())?;
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(&path)
+        .map_err(|e| format!("Could not create {}: {}", name, e))?;
+    file.write_all(&bytes)
+        .and_then(|_| file.sync_all())
+        .map_err(|e| format!("Could not write {}: {}", name, e))?;
+    println!("🔐 Created {}", path.display());
+    Ok(bytes)
+}
+
+/// Keep the file private to its owner, tightening it if it was loosened
+fn restrict_permissions(path: &Path) -> Result<(), String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(path)
+            .map_err(|e| e.to_string())?
+            .permissions()
+            .mode();
+        if mode & 0o077 != 0 {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// The stored key check, with where the key it was made with came from
+#[derive(Serialize, Deserialize)]
+struct KeyCheck {
+    /// "passphrase" or "file"
+    source: String,
+    value: String,
+}
+
+fn passphrase() -> Option<String> {
+    std::env::var(PASSPHRASE_ENV).ok().filter(|p| !p.is_empty())
+}
+
+fn key_source() -> &'static str {
+    if passphrase().is_some() {
+        "passphrase"
+    } else {
+        "file"
+    }
+}
+
+fn describe_source(source: &str) -> String {
+    match source {
+        "passphrase" => format!("the {} passphrase", PASSPHRASE_ENV),
+        _ => format!("the key file {}", anthyre_dir().join(KEY_FILE).display()),
+    }
+}
+
+fn load_key() -> Result<Key<Aes256Gcm>, String> {
+    match passphrase() {
+        Some(passphrase) => {
+            let salt = read_or_create(SALT_FILE, SALT_LEN)?;
+            let mut key = [0u8; KEY_LEN];
+            Argon2::default()
+                .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+                .map_err(|e| format!("Could not derive key from passphrase: {}", e))?;
+            Ok(key.into())
+        }
+        None => {
+            let key = read_or_create(KEY_FILE, KEY_LEN)?;
+            Ok(*Key::<Aes256Gcm>::from_slice(&key))
+        }
+    }
+}
+
+fn cipher() -> Result<Aes256Gcm, String> {
+    KEY.as_ref()
+        .map(Aes256Gcm::new)
+        .map_err(|e| format!("Secret key unavailable: {}", e))
+}
+
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(PREFIX)
+}
+
+/// Encrypt `plain` for storage as `enc:v1:<base64 nonce + ciphertext>`
+pub fn encrypt(plain: &str) -> Result<String, String> {
+    encrypt_with(&cipher()?, plain)
+}
+
+/// Decrypt a stored value; plaintext from before encryption is returned as is
+pub fn decrypt(stored: &str) -> Result<String, String> {
+    let Some(encoded) = stored.strip_prefix(PREFIX) else {
+        return Ok(stored.to_string());
+    };
+    decrypt_with(&cipher()?, encoded)
+}
+
+fn encrypt_with(cipher: &Aes256Gcm, plain: &str) -> Result<String, String> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plain.as_bytes())
+        .map_err(|_| "Could not encrypt secret".to_string())?;
+
+    let mut bytes = nonce.to_vec();
+    bytes.extend_from_slice(&ciphertext);
+    Ok(format!("{}{}", PREFIX, STANDARD.encode(bytes)))
+}
+
+/// Decrypt the part of a stored value after the prefix
+fn decrypt_with(cipher: &Aes256Gcm, encoded: &str) -> Result<String, String> {
+    let bytes = STANDARD
+        .decode(encoded)
+        .map_err(|_| "Stored secret is corrupt".to_string())?;
+    if bytes.len() < NONCE_LEN {
+        return Err("Stored secret is corrupt".into());
+    }
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    let plain = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| {
+            "Could not decrypt stored secret, please log in with Google again".to_string()
+        })?;
+    String::from_utf8(plain).map_err(|e| e.to_string())
+}
+
+/// Make sure the key in use is the one the stored secrets were encrypted with,
+/// so a changed passphrase or a lost key file fails once, at startup, instead
+/// of on every secret. The first run stores the check.
+pub fn verify_key(conn: &Connection) -> Result<(), String> {
+    let cipher = cipher()?;
+    let stored = settings::get(conn, KEY_CHECK_SETTING)
+        .map_err(|e| e.to_string())?
+        .and_then(|json| serde_json::from_str::<KeyCheck>(&json).ok());
+
+    match stored {
+        Some(check) => match check.value.strip_prefix(PREFIX) {
+            Some(encoded) if decrypt_with(&cipher, encoded).is_ok_and(|p| p == KEY_CHECK_PLAIN) => {
+                Ok(())
+            }
+            _ => Err(format!(
+                "The secret key from {} can't decrypt the stored secrets, which were encrypted \
+                 with the key from {}. Restore that key and restart Anthyre.",
+                describe_source(key_source()),
+                describe_source(&check.source)
+            )),
+        },
+        None => {
+            let check = KeyCheck {
+                source: key_source().to_string(),
+                value: encrypt_with(&cipher, KEY_CHECK_PLAIN)?,
+            };
+            let json = serde_json::to_string(&check).map_err(|e| e.to_string())?;
+            settings::set(conn, KEY_CHECK_SETTING, &json).map_err(|e| e.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_cipher(byte: u8) -> Aes256Gcm {
+        Aes256Gcm::new(&[byte; KEY_LEN].into())
+    }
+
+    fn encoded(stored: &str) -> &str {
+        stored.strip_prefix(PREFIX).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let cipher = test_cipher(7);
+        let stored = encrypt_with(&cipher, "client-secret").unwrap();
+        assert!(is_encrypted(&stored));
+        assert!(!stored.contains("client-secret"));
+        assert_eq!(decrypt_with(&cipher, encoded(&stored)).unwrap(), "client-secret");
+    }
+
+    #[test]
+    fn nonce_differs_per_encryption() {
+        let cipher = test_cipher(7);
+        let first = encrypt_with(&cipher, "same").unwrap();
+        let second = encrypt_with(&cipher, "same").unwrap();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn plaintext_passes_through() {
+        assert!(!is_encrypted("ya29.legacy-token"));
+        assert_eq!(decrypt("ya29.legacy-token").unwrap(), "ya29.legacy-token");
+        assert_eq!(decrypt("").unwrap(), "");
+    }
+
+    #[test]
+    fn other_key_fails() {
+        let stored = encrypt_with(&test_cipher(7), "client-secret").unwrap();
+        assert!(decrypt_with(&test_cipher(8), encoded(&stored)).is_err());
+    }
+
+    #[test]
+    fn corrupt_value_fails() {
+        let cipher = test_cipher(7);
+        assert!(decrypt_with(&cipher, "not base64!").is_err());
+        assert!(decrypt_with(&cipher, &STANDARD.encode([0u8; 4])).is_err());
+
+        let stored = encrypt_with(&cipher, "client-secret").unwrap();
+        let mut bytes = STANDARD.decode(encoded(&stored)).unwrap();
+        *bytes.last_mut().unwrap() ^= 1;
+        assert!(decrypt_with(&cipher, &STANDARD.encode(bytes)).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use crate::database::{self, settings};
+use crate::users::{active_user_id, LEGACY_USER_ID};
+
+/// IANA zone name, e.g. "Europe/Berlin"; the system zone is used when unset.
+/// Kept per account as `<this>:<user id>`; without a user id it is the legacy account's.
+const TIMEZONE_SETTING: &str = "timezone";
+/// Longest span a multi-day view may cover
+const MAX_SPAN_DAYS: i64 = 31;
+
+fn system_timezone() -> Tz {
+    iana_time_zone::get_timezone()
+        .ok()
+        .and_then(|name| name.parse().ok())
+        .unwrap_or(Tz::UTC)
+}
+
+/// Zone days and blocks are counted in. Locks the database, so call it before
+/// taking the connection yourself.
+pub fn user_timezone() -> Tz {
+    let conn = database::connection();
+    let user_id = active_user_id(&conn);
+    let mut name = settings::get(&conn, &timezone_key(user_id)).ok().flatten();
+    if name.is_none() && user_id == LEGACY_USER_ID {
+        name = settings::get(&conn, TIMEZONE_SETTING).ok().flatten();
+    }
+    name.and_then(|name| name.parse().ok()).unwrap_or_else(system_timezone)
+}
+
+fn timezone_key(user_id: i64) -> String {
+    format!("{}:{}", TIMEZONE_SETTING, user_id)
+}
+
+/// Current date in `tz`
+pub fn today(tz: &Tz) -> NaiveDate {
+    Utc::now().with_timezone(tz).date_naive()
+}
+
+/// First instant of `date` in `tz`. Where a DST change skips midnight the day
+/// starts at the first valid local time after it.
+pub fn start_of_day(tz: &Tz, date: NaiveDate) -> DateTime<Utc> {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+    (0..=24 * 4)
+        .map(|quarter| midnight + Duration::minutes(15 * quarter))
+        .find_map(|local| tz.from_local_datetime(&local).earliest())
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or_else(|| midnight.and_utc())
+}
+
+/// `[start, end)` of `date` in `tz`, in UTC. 23 or 25 hours long on DST changes.
+pub fn day_range(tz: &Tz, date: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+    (
+        start_of_day(tz, date),
+        start_of_day(tz, date + Duration::days(1)),
+    )
+}
+
+/// Parse a `YYYY-MM-DD` date
+pub fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|e| format!("Invalid date '{}': {}", value, e))
+}
+
+/// Inclusive date span from optional `YYYY-MM-DD` bounds: today when no
+/// start is given, a single day when no end is given
+pub fn date_span(
+    tz: &Tz,
+    start: Option<String>,
+    end: Option<String>,
+) -> Result<(NaiveDate, NaiveDate), String> {
+    let first = match start {
+        Some(d) => parse_date(&d)?,
+        None => today(tz),
+    };
+    let last = match end {
+        Some(d) => parse_date(&d)?,
+        None => first,
+    };
+
+    if last < first {
+        return Err("End date must not be before start date".into());
+    }
+    if (last - first).num_days() >= MAX_SPAN_DAYS {
+        return Err(format!("Date ranges are limited to {} days", MAX_SPAN_DAYS));
+    }
+    Ok((first, last))
+}
+
+#[tauri::command]
+pub fn get_timezone() -> String {
+    user_timezone().name().to_string()
+}
+
+/// Set the zone days are counted in (an IANA name); `None` follows the system zone
+#[tauri::command]
+pub fn set_timezone(name: Option<String>) -> Result<(), String> {
+    let conn = database::connection();
+    let user_id = active_user_id(&conn);
+    let key = timezone_key(user_id);
+    match name {
+        Some(name) => {
+            let tz: Tz = name
+                .parse()
+                .map_err(|e| format!("Unknown timezone '{}': {}", name, e))?;
+            settings::set(&conn, &key, tz.name())
+        }
+        None => settings::delete(&conn, &key),
+    }
+    .map_err(|e| e.to_string())?;
+    if user_id == LEGACY_USER_ID {
+        // Replaced by the account's own setting
+        settings::delete(&conn, TIMEZONE_SETTING).map_err(|e| e.to_string())?;
+    }
+
+    // All-day events are cached at the old zone's midnights
+    crate::activity::event_cache::reset(&conn, user_id).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tz(name: &str) -> Tz {
+        name.parse().unwrap()
+    }
+
+    fn date(value: &str) -> NaiveDate {
+        parse_date(value).unwrap()
+    }
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn regular_day_is_24_hours() {
+        let (start, end) = day_range(&tz("Europe/Berlin"), date("2024-05-02"));
+        assert_eq!(start, utc("2024-05-01T22:00:00Z"));
+        assert_eq!(end - start, Duration::hours(24));
+    }
+
+    #[test]
+    fn spring_forward_day_is_23_hours() {
+        let (start, end) = day_range(&tz("Europe/Berlin"), date("2024-03-31"));
+        assert_eq!(start, utc("2024-03-30T23:00:00Z"));
+        assert_eq!(end, utc("2024-03-31T22:00:00Z"));
+        assert_eq!(end - start, Duration::hours(23));
+    }
+
+    #[test]
+    fn fall_back_day_is_25_hours() {
+        let (start, end) = day_range(&tz("Europe/Berlin"), date("2024-10-27"));
+        assert_eq!(start, utc("2024-10-26T22:00:00Z"));
+        assert_eq!(end, utc("2024-10-27T23:00:00Z"));
+        assert_eq!(end - start, Duration::hours(25));
+    }
+
+    #[test]
+    fn consecutive_days_share_their_boundary() {
+        let tz = tz("America/New_York");
+        let (_, end) = day_range(&tz, date("2024-03-09"));
+        let (start, _) = day_range(&tz, date("2024-03-10"));
+        assert_eq!(end, start);
+    }
+
+    #[test]
+    fn day_without_midnight_starts_at_the_first_valid_time() {
+        // Santiago skipped from 00:00 to 01:00 on 2024-09-08
+        let start = start_of_day(&tz("America/Santiago"), date("2024-09-08"));
+        let local = start.with_timezone(&tz("America/Santiago"));
+        assert_eq!(local.format("%Y-%m-%d %H:%M").to_string(), "2024-09-08 01:00");
+        assert_eq!(start, utc("2024-09-08T04:00:00Z"));
+    }
+
+    #[test]
+    fn date_span_defaults_to_a_single_day() {
+        let tz = tz("UTC");
+        let (first, last) = date_span(&tz, Some("2024-05-02".into()), None).unwrap();
+        assert_eq!((first, last), (date("2024-05-02"), date("2024-05-02")));
+    }
+
+    #[test]
+    fn date_span_rejects_reversed_and_long_ranges() {
+        let tz = tz("UTC");
+        assert!(date_span(&tz, Some("2024-05-02".into()), Some("2024-05-01".into())).is_err());
+        assert!(date_span(&tz, Some("2024-01-01".into()), Some("2024-03-01".into())).is_err());
+    }
+}
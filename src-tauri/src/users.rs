@@ -0,0 +1,140 @@
+use chrono::Utc;
+use reqwest::Client;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+
+use crate::database::{self, settings};
+
+/// Account whose data is recorded and shown
+const ACTIVE_USER_SETTING: &str = "active_user_id";
+/// Owner of everything recorded before accounts existed. The first account
+/// to sign in gets this id, and with it that data.
+pub const LEGACY_USER_ID: i64 = 1;
+const USERINFO_URL: &str = "https://www.googleapis.com/oauth2/v3/userinfo";
+
+#[derive(Debug, Serialize, Clone)]
+pub struct User {
+    pub id: i64,
+    pub name: String,
+    pub email: String,
+    pub is_active: bool,
+    pub created_at: String,
+}
+
+/// The parts of Google's userinfo answer Anthyre keeps
+#[derive(Debug, Deserialize)]
+pub struct GoogleProfile {
+    pub email: String,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// Id of the active account. Takes the connection the caller already holds.
+pub fn active_user_id(conn: &Connection) -> i64 {
+    settings::get(conn, ACTIVE_USER_SETTING)
+        .ok()
+        .flatten()
+        .and_then(|id| id.parse().ok())
+        .unwrap_or(LEGACY_USER_ID)
+}
+
+pub fn set_active_user(conn: &Connection, user_id: i64) -> rusqlite::Result<()> {
+    settings::set(conn, ACTIVE_USER_SETTING, &user_id.to_string())
+}
+
+/// Profile of the account an access token belongs to (needs the
+/// `userinfo.email` and `userinfo.profile` scopes)
+pub async fn fetch_profile(client: &Client, access_token: &str) -> Result<GoogleProfile, String> {
+    let resp = client
+        .get(USERINFO_URL)
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !resp.status().is_success() {
+        return Err(format!("Failed to fetch Google profile: {}", resp.status()));
+    }
+    resp.json().await.map_err(|e| e.to_string())
+}
+
+/// Insert the account for `profile`, or refresh its name; returns its id
+pub fn upsert_user(conn: &Connection, profile: &GoogleProfile) -> rusqlite::Result<i64> {
+    let now = Utc::now().to_rfc3339();
+    let name = profile.name.as_deref().unwrap_or(&profile.email);
+    conn.execute(
+        "INSERT INTO users (name, email, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?3)
+         ON CONFLICT(email) DO UPDATE SET name = excluded.name, updated_at = excluded.updated_at",
+        params![name, profile.email, now],
+    )?;
+    conn.query_row(
+        "SELECT id FROM users WHERE email = ?1",
+        params![profile.email],
+        |row| row.get(0),
+    )
+}
+
+fn user_from_row(row: &Row, active_id: i64) -> rusqlite::Result<User> {
+    let id: i64 = row.get(0)?;
+    Ok(User {
+        id,
+        name: row.get(1)?,
+        email: row.get(2)?,
+        is_active: id == active_id,
+        created_at: row.get(3)?,
+    })
+}
+
+fn find_user(conn: &Connection, user_id: i64) -> rusqlite::Result<Option<User>> {
+    let active_id = active_user_id(conn);
+    conn.query_row(
+        "SELECT id, name, email, created_at FROM users WHERE id = ?1",
+        params![user_id],
+        |row| user_from_row(row, active_id),
+    )
+    .optional()
+}
+
+/// The active account, `None` before anyone signed in
+#[tauri::command]
+pub fn get_current_user() -> Result<Option<User>, String> {
+    let conn = database::connection();
+    find_user(&conn, active_user_id(&conn)).map_err(|e| e.to_string())
+}
+
+/// Every account that signed in on this machine
+#[tauri::command]
+pub fn list_users() -> Result<Vec<User>, String> {
+    let conn = database::connection();
+    let active_id = active_user_id(&conn);
+    let mut stmt = conn
+        .prepare("SELECT id, name, email, created_at FROM users ORDER BY name")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| user_from_row(row, active_id))
+        .map_err(|e| e.to_string())?;
+    rows.collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(|e| e.to_string())
+}
+
+/// Make another signed-in account the active one
+#[tauri::command]
+pub fn switch_user(user_id: i64) -> Result<User, String> {
+    // Sessions and their distractions belong to the account that started them
+    if crate::pomodoro::get_pomodoro_status()?.is_some() {
+        return Err("Stop the running pomodoro before switching accounts".into());
+    }
+
+    let conn = database::connection();
+    if find_user(&conn, user_id)
+        .map_err(|e| e.to_string())?
+        .is_none()
+    {
+        return Err(format!("User {} not found", user_id));
+    }
+    set_active_user(&conn, user_id).map_err(|e| e.to_string())?;
+    find_user(&conn, user_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("User {} not found", user_id))
+}
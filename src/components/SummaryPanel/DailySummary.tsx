@@ -1,31 +1,15 @@
-import { useEffect, useState } from "react";
 import { Button } from "@/components/ui/button";
 import { RefreshCw } from "lucide-react";
-import { syncCalendar, daily_summary } from "@/lib/utils";
+import { type DayReview } from "@/lib/utils";
 import { Card, CardHeader, CardContent } from "@/components/ui/card";
 
-export const DailySummaryCard = () => {
-  const [summary, setSummary] = useState<string | null>(null);
-  const [loading, setLoading] = useState<boolean>(true);
-
-  const fetchSummary = async () => {
-    setLoading(true);
-    try {
-      const result = await daily_summary();
-      setSummary(result as string);
-    } catch (err: unknown) {
-      console.error("Failed to fetch summary:", err);
-      const errorMsg = err instanceof Error ? err.message : String(err);
-      setSummary(`Failed to load summary: ${errorMsg}`);
-    } finally {
-      setLoading(false);
-    }
-  };
-
-  useEffect(() => {
-    fetchSummary();
-  }, []);
+type Props = {
+  review: DayReview | null;
+  loading: boolean;
+  onRefresh: () => void;
+};
 
+export const DailySummaryCard = ({ review, loading, onRefresh }: Props) => {
   return (
     <Card className="bg-gray-800/50 border-gray-800">
       <CardHeader className="flex flex-row justify-between items-center pt-6 space-y-0">
@@ -34,20 +18,34 @@ export const DailySummaryCard = () => {
           variant="ghost"
           size="icon"
           className="p-1 text-gray-400 hover:text-white hover:bg-gray-700"
-          onClick={async () => {
-            await syncCalendar();
-          }}
+          onClick={onRefresh}
         >
           <RefreshCw className="h-4 w-4" />
         </Button>
       </CardHeader>
-      <CardContent className="pb-6">
-        <p className="text-sm text-gray-400 leading-relaxed">
-          {loading ? "Loading daily summary..." : summary}
-        </p>
+      <CardContent className="pb-6 space-y-3">
+        {loading && (
+          <p className="text-sm text-gray-400">Loading daily summary...</p>
+        )}
+        {!loading && !review && (
+          <p className="text-sm text-gray-400">Failed to load summary.</p>
+        )}
+        {!loading && review && (
+          <>
+            <p className="text-sm text-gray-400 leading-relaxed whitespace-pre-line">
+              {review.summary}
+            </p>
+            {review.strategy && (
+              <p className="text-sm text-gray-300 leading-relaxed">
+                <span className="font-medium text-white">Strategy: </span>
+                {review.strategy}
+              </p>
+            )}
+          </>
+        )}
       </CardContent>
     </Card>
   );
 };
 
-export default DailySummaryCard;
\ No newline at end of file
+export default DailySummaryCard;
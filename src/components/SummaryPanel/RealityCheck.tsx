@@ -1,7 +1,14 @@
 import { AlertTriangle, XCircle } from 'lucide-react';
 import { Card, CardHeader, CardContent } from '@/components/ui/card';
+import { type RealityCheck } from '@/lib/utils';
+
+type Props = {
+  reality?: RealityCheck;
+};
+
+export const RealityCheckCard = ({ reality }: Props) => {
+  const score = Math.round(reality?.planned_vs_actual ?? 0);
 
-export const RealityCheckCard = () => {
   return (
     <Card className="bg-gray-800/50 border-gray-800">
       <CardHeader className="pt-6">
@@ -10,35 +17,43 @@ export const RealityCheckCard = () => {
       <CardContent className="pb-6">
         <div className="flex gap-4 justify-between items-center mb-2">
           <p className="text-sm font-medium text-gray-400">Reality Score</p>
-          <p className="text-lg font-bold text-white">85%</p>
+          <p className="text-lg font-bold text-white">{score}%</p>
         </div>
         <div className="w-full bg-gray-700 rounded-full h-2.5 mb-4">
           <div
             className="bg-blue-600 h-2.5 rounded-full"
-            style={{ width: "85%" }}
+            style={{ width: `${score}%` }}
           ></div>
         </div>
-        <div className="space-y-3">
-          <div className="flex items-start gap-3">
-            <AlertTriangle className="h-5 w-5 text-yellow-400 mt-1" />
-            <div>
-              <p className="font-medium text-white">Client Call ran 15 mins over</p>
-              <p className="text-sm text-gray-400">
-                Planned: 11:00 AM, Actual: 11:00 AM - 11:15 AM
-              </p>
+        {reality && (
+          <div className="space-y-3">
+            <div className="flex items-start gap-3">
+              <AlertTriangle className="h-5 w-5 text-yellow-400 mt-1" />
+              <div>
+                <p className="font-medium text-white">
+                  {reality.time_overruns} plan(s) ran over
+                </p>
+                <p className="text-sm text-gray-400">
+                  Focus score: {Math.round(reality.focus_score)}%
+                </p>
+              </div>
             </div>
-          </div>
-          <div className="flex items-start gap-3">
-            <XCircle className="h-5 w-5 text-red-500 mt-1" />
-            <div>
-              <p className="font-medium text-white">Unplanned Social Media Break</p>
-              <p className="text-sm text-gray-400">Between 2:00 PM - 3:00 PM</p>
+            <div className="flex items-start gap-3">
+              <XCircle className="h-5 w-5 text-red-500 mt-1" />
+              <div>
+                <p className="font-medium text-white">
+                  {reality.distractions_detected} distracted block(s)
+                </p>
+                <p className="text-sm text-gray-400">
+                  Away: {Math.round(reality.afk_minutes)} min
+                </p>
+              </div>
             </div>
           </div>
-        </div>
+        )}
       </CardContent>
     </Card>
   );
 };
 
-export default RealityCheckCard;
\ No newline at end of file
+export default RealityCheckCard;
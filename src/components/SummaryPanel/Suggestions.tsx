@@ -1,7 +1,18 @@
 import { CheckCircle } from 'lucide-react';
 import { Card, CardHeader, CardContent } from '@/components/ui/card';
+import { type PriorityLevel, type Suggestion } from '@/lib/utils';
 
-export const SuggestionsCard = () => {
+const PRIORITY_COLORS: Record<PriorityLevel, string> = {
+  High: 'text-red-500',
+  Medium: 'text-yellow-400',
+  Low: 'text-green-500',
+};
+
+type Props = {
+  suggestions?: Suggestion[];
+};
+
+export const SuggestionsCard = ({ suggestions = [] }: Props) => {
   return (
     <Card className="bg-gray-800/50 border-gray-800">
       <CardHeader className="pt-6">
@@ -10,29 +21,25 @@ export const SuggestionsCard = () => {
         </h3>
       </CardHeader>
       <CardContent className="pb-6">
+        {suggestions.length === 0 && (
+          <p className="text-sm text-gray-400">No suggestions yet.</p>
+        )}
         <ul className="space-y-3">
-          <li className="flex items-start gap-3">
-            <CheckCircle className="h-5 w-5 text-green-500 mt-1" />
-            <p className="text-sm text-gray-300">
-              Set hard stops for meetings to stay on schedule.
-            </p>
-          </li>
-          <li className="flex items-start gap-3">
-            <CheckCircle className="h-5 w-5 text-green-500 mt-1" />
-            <p className="text-sm text-gray-300">
-              Use a website blocker during focused work blocks.
-            </p>
-          </li>
-          <li className="flex items-start gap-3">
-            <CheckCircle className="h-5 w-5 text-green-500 mt-1" />
-            <p className="text-sm text-gray-300">
-              Schedule short, intentional breaks to avoid burnout.
-            </p>
-          </li>
+          {suggestions.map((suggestion, i) => (
+            <li key={i} className="flex items-start gap-3">
+              <CheckCircle
+                className={`h-5 w-5 mt-1 ${PRIORITY_COLORS[suggestion.priority]}`}
+              />
+              <div>
+                <p className="font-medium text-white">{suggestion.title}</p>
+                <p className="text-sm text-gray-300">{suggestion.description}</p>
+              </div>
+            </li>
+          ))}
         </ul>
       </CardContent>
     </Card>
   );
 };
 
-export default SuggestionsCard;
\ No newline at end of file
+export default SuggestionsCard;
@@ -22,9 +22,70 @@ export async function init(): Promise<boolean> {
   return await invoke<boolean>("check_calendar_token");
 }
 
-export async function daily_summary() {
+export type User = {
+  id: number;
+  name: string;
+  email: string;
+  is_active: boolean;
+  created_at: string;
+};
+
+export async function currentUser() {
+  try {
+    return await invoke<User | null>("get_current_user");
+  } catch (err) {
+    console.error("can't fetch current user:", err);
+    return null;
+  }
+}
+
+export async function listUsers() {
   try {
-    const result = await invoke("get_daily_summary");
+    return await invoke<User[]>("list_users");
+  } catch (err) {
+    console.error("can't list users:", err);
+    return [];
+  }
+}
+
+export async function switchUser(userId: number) {
+  return await invoke<User>("switch_user", { userId });
+}
+
+export type PriorityLevel = "High" | "Medium" | "Low";
+
+export type Suggestion = {
+  title: string;
+  description: string;
+  priority: PriorityLevel;
+};
+
+export type RealityCheck = {
+  planned_vs_actual: number;
+  distractions_detected: number;
+  time_overruns: number;
+  focus_score: number;
+  afk_minutes: number;
+};
+
+export type DayReview = {
+  summary: string;
+  strategy: string;
+  reality_check: RealityCheck;
+  suggestions: Suggestion[];
+};
+
+export async function daily_summary(
+  date?: string,
+  endDate?: string,
+  regenerate?: boolean
+) {
+  try {
+    const result = await invoke<DayReview>("get_daily_summary", {
+      date,
+      endDate,
+      regenerate,
+    });
     console.log("daily summary:", result);
     return result;
   } catch (err) {
@@ -33,6 +94,19 @@ export async function daily_summary() {
   }
 }
 
+export async function summary_history(
+  startDate?: string,
+  endDate?: string,
+  limit?: number
+) {
+  try {
+    return await invoke("get_summary_history", { startDate, endDate, limit });
+  } catch (err) {
+    console.error("can't fetch summary history:", err);
+    return null;
+  }
+}
+
 export async function updateHours() {
   try {
     // Here you might call another Rust command, for now just reuse login
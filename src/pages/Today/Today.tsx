@@ -6,6 +6,7 @@ import { Card, CardHeader, CardContent } from "@/components/ui/card";
 import DailySummary from "@/components/SummaryPanel/DailySummary";
 import Suggestions from "@/components/SummaryPanel/Suggestions";
 import RealityCheck from "@/components/SummaryPanel/RealityCheck";
+import { daily_summary, syncCalendar, type DayReview } from "@/lib/utils";
 
 type Event = {
   summary: string;
@@ -14,23 +15,52 @@ type Event = {
   end: string;
 };
 
+type ActivityBlock = {
+  title: string;
+  llm_summary?: string;
+  block_start: string;
+  block_end: string;
+};
+
 type Batch = {
   start_hour: number;
   end_hour: number;
+  start_minute: number;
+  end_minute: number;
   label: string;
   is_event: boolean;
   events?: Event[];
+  actual?: ActivityBlock[];
+};
+
+type DayBatches = {
+  date: string;
+  batches: Batch[];
+  all_day: Event[];
 };
 
 export default function Today() {
   const [batches, setBatches] = useState<Batch[]>([]);
+  const [allDay, setAllDay] = useState<Event[]>([]);
   const [loading, setLoading] = useState(true);
+  const [review, setReview] = useState<DayReview | null>(null);
+  const [reviewLoading, setReviewLoading] = useState(true);
+
+  const fetchReview = async (regenerate?: boolean) => {
+    setReviewLoading(true);
+    setReview(await daily_summary(undefined, undefined, regenerate));
+    setReviewLoading(false);
+  };
 
   useEffect(() => {
-    invoke<Batch[]>("fetch_batches")
-      .then((res) => setBatches(res))
+    invoke<DayBatches>("fetch_batches")
+      .then((res) => {
+        setBatches(res.batches);
+        setAllDay(res.all_day);
+      })
       .catch((err) => console.error("Failed to fetch batches:", err))
       .finally(() => setLoading(false));
+    fetchReview();
   }, []);
   console.log(batches);
 
@@ -46,6 +76,24 @@ export default function Today() {
           <p className="text-gray-400 text-center">No data available</p>
         )}
 
+        {allDay.length > 0 && (
+          <Card className="border-gray-800 bg-slate-900">
+            <CardHeader className="px-4 py-3 bg-gray-800/50">
+              <h3 className="font-semibold text-white">All day</h3>
+            </CardHeader>
+            <CardContent className="p-4 flex flex-wrap gap-2">
+              {allDay.map((event, i) => (
+                <div
+                  key={i}
+                  className="px-3 py-2 bg-gray-800 rounded-md text-white"
+                >
+                  {event.summary}
+                </div>
+              ))}
+            </CardContent>
+          </Card>
+        )}
+
         {batches.map((batch, index) => (
           <Card
             key={index}
@@ -55,7 +103,7 @@ export default function Today() {
           >
             <CardHeader className="px-4 py-3 bg-gray-800/50 flex justify-between items-center">
               <h3 className="font-semibold text-white">
-                {batch.label} ({batch.start_hour}:00 → {batch.end_hour}:59)
+                {batch.label}
               </h3>
             </CardHeader>
             <CardContent className="p-4 grid grid-cols-2 gap-4">
@@ -63,8 +111,7 @@ export default function Today() {
                 <h4 className="font-medium text-gray-400 mb-2">Planned</h4>
                 <div className="space-y-2">
                   {batch.events
-                    ?.filter((event) => !event.description?.includes("#ai"))
-                    .map((event, i) => (
+                    ?.map((event, i) => (
                       <div
                         key={i}
                         className="p-3 bg-gray-800 rounded-md text-white"
@@ -82,12 +129,9 @@ export default function Today() {
                 <h4 className="font-medium text-gray-400 mb-2">Actual</h4>
                 <div className="space-y-2">
                   {(() => {
-                    const actualEvents =
-                      batch.events?.filter((event) =>
-                        event.description?.includes("#ai")
-                      ) || [];
+                    const actualBlocks = batch.actual || [];
 
-                    if (actualEvents.length === 0) {
+                    if (actualBlocks.length === 0) {
                       return (
                         <p className="p-3 bg-gray-800 rounded-md text-gray-500 italic">
                           Empty
@@ -95,12 +139,12 @@ export default function Today() {
                       );
                     }
 
-                    return actualEvents.map((event, i) => (
+                    return actualBlocks.map((block, i) => (
                       <div
                         key={i}
                         className="p-3 bg-gray-800 rounded-md text-white"
                       >
-                        {event.summary}
+                        {block.title}
                       </div>
                     ));
                   })()}
@@ -113,9 +157,16 @@ export default function Today() {
 
       {/* Right Panel */}
       <div className="col-span-1 space-y-8">
-        <DailySummary />
-        <RealityCheck />
-        <Suggestions />
+        <DailySummary
+          review={review}
+          loading={reviewLoading}
+          onRefresh={async () => {
+            await syncCalendar();
+            await fetchReview(true);
+          }}
+        />
+        <RealityCheck reality={review?.reality_check} />
+        <Suggestions suggestions={review?.suggestions} />
       </div>
     </div>
   );